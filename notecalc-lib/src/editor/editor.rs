@@ -1,4 +1,4 @@
-use crate::editor::editor_content::{EditorCommand, EditorContent, JumpMode};
+use crate::editor::editor_content::{EditorCommand, EditorContent, JumpMode, RowsSnapshot};
 use smallvec::alloc::fmt::Debug;
 use std::ops::{Range, RangeInclusive};
 
@@ -22,6 +22,52 @@ pub enum EditorInputEvent {
     Char(char),
 }
 
+impl EditorInputEvent {
+    /// Parses a keystroke described like "C-S-Left", "Enter" or "a".
+    /// The modifier prefixes are "C-" (ctrl), "S-" (shift) and "A-" (alt).
+    pub fn parse_spec(spec: &str) -> Option<(EditorInputEvent, InputModifiers)> {
+        let mut modifiers = InputModifiers::none();
+        let mut key = spec;
+        while key.chars().count() > 2 {
+            if let Some(rest) = key.strip_prefix("C-") {
+                modifiers.ctrl = true;
+                key = rest;
+            } else if let Some(rest) = key.strip_prefix("S-") {
+                modifiers.shift = true;
+                key = rest;
+            } else if let Some(rest) = key.strip_prefix("A-") {
+                modifiers.alt = true;
+                key = rest;
+            } else {
+                break;
+            }
+        }
+        let input = match key {
+            "Left" => EditorInputEvent::Left,
+            "Right" => EditorInputEvent::Right,
+            "Up" => EditorInputEvent::Up,
+            "Down" => EditorInputEvent::Down,
+            "Home" => EditorInputEvent::Home,
+            "End" => EditorInputEvent::End,
+            "Esc" => EditorInputEvent::Esc,
+            "PageUp" => EditorInputEvent::PageUp,
+            "PageDown" => EditorInputEvent::PageDown,
+            "Enter" => EditorInputEvent::Enter,
+            "Backspace" => EditorInputEvent::Backspace,
+            "Del" => EditorInputEvent::Del,
+            "Tab" => EditorInputEvent::Tab,
+            _ => {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(ch), None) => EditorInputEvent::Char(ch),
+                    _ => return None,
+                }
+            }
+        };
+        Some((input, modifiers))
+    }
+}
+
 #[repr(C)]
 #[derive(Eq, PartialEq, Debug, Clone, Copy)]
 pub struct InputModifiers {
@@ -194,12 +240,18 @@ impl Selection {
         }
     }
 
+    /// a selection whose end is the same as its start (e.g. constructed directly
+    /// via the public fields) is not a range
     pub fn is_range(&self) -> bool {
-        self.end.is_some()
+        self.end.map(|end| end != self.start).unwrap_or(false)
+    }
+
+    pub fn normalized(&self) -> Selection {
+        Selection::range(self.start, self.end.unwrap_or(self.start))
     }
 
     pub fn is_range_ordered(&self) -> Option<(Pos, Pos)> {
-        if let Some(end) = self.end {
+        if let Some(end) = self.end.filter(|end| *end != self.start) {
             let end_index = end.row * 1024 + end.column;
             let start_index = self.start.row * 1024 + self.start.column;
             if end_index < start_index {
@@ -293,6 +345,115 @@ impl RowModificationType {
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SelectionSummary {
+    pub line_count: usize,
+    /// line breaks are counted as one char
+    pub char_count: usize,
+    pub is_multiline: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OverflowPolicy {
+    Wrap,
+    Truncate,
+    /// nothing is inserted if any of the lines would overflow
+    Reject,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DragMode {
+    Char,
+    Word,
+    Line,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InputEffect {
+    NoChange,
+    SelectionChanged,
+    /// the index of the first modified row
+    TextChanged(usize),
+    /// only the trailing whitespace of the row was changed, so its meaning did not change
+    WhitespaceOnly(usize),
+    Rejected,
+    /// Enter was pressed in single line mode
+    Submit,
+}
+
+/// The cursor and the selection in a flat, FFI friendly form.
+/// Without selection, the sel_* fields are equal to the cursor position.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CursorState {
+    pub row: usize,
+    pub column: usize,
+    pub sel_start_row: usize,
+    pub sel_start_col: usize,
+    pub sel_end_row: usize,
+    pub sel_end_col: usize,
+    pub has_selection: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ViewState {
+    pub selection: Selection,
+    pub last_column_index: usize,
+    pub mark: Option<Pos>,
+    pub scroll_top: usize,
+}
+
+struct ReplacedRange {
+    start: Pos,
+    end: Pos,
+    // the number of chars after `end` in its row
+    end_tail_len: usize,
+    line_count: usize,
+}
+
+impl ReplacedRange {
+    fn new<T: Default + Clone + Debug>(start: Pos, end: Pos, content: &EditorContent<T>) -> Self {
+        ReplacedRange {
+            start,
+            end,
+            end_tail_len: content.line_len(end.row).saturating_sub(end.column),
+            line_count: content.line_count(),
+        }
+    }
+
+    /// Positions before the range stay, positions inside it are moved to its start,
+    /// and the ones after it follow their text. A position at the start of the range
+    /// is moved only if `shift_at_start` is set.
+    fn map<T: Default + Clone + Debug>(
+        &self,
+        pos: Pos,
+        shift_at_start: bool,
+        content: &EditorContent<T>,
+    ) -> Pos {
+        let is_before = |a: Pos, b: Pos| (a.row, a.column) < (b.row, b.column);
+        if is_before(pos, self.start) || (pos == self.start && !shift_at_start) {
+            return pos;
+        }
+        if pos.row > self.end.row {
+            pos.with_row(pos.row + content.line_count() - self.line_count)
+        } else if is_before(pos, self.end) {
+            self.start
+        } else {
+            // the rest of the row is at the end of `end_row`, but it could have been
+            // wrapped into more rows, so it is walked backwards from there
+            let mut dist_from_tail_end = self
+                .end_tail_len
+                .saturating_sub(pos.column - self.end.column);
+            let mut row = self.end.row + content.line_count() - self.line_count;
+            while dist_from_tail_end > content.line_len(row) && row > 0 {
+                dist_from_tail_end -= content.line_len(row);
+                row -= 1;
+            }
+            Pos::from_row_column(row, content.line_len(row) - dist_from_tail_end)
+        }
+    }
+}
+
 pub struct Editor {
     selection: Selection,
     last_column_index: usize,
@@ -300,6 +461,28 @@ pub struct Editor {
     next_blink_at: u32,
     modif_time_treshold_expires_at: u32,
     show_cursor: bool,
+    wide_char_aware_click: bool,
+    last_input_effect: InputEffect,
+    wrap_width: Option<usize>,
+    force_soft_tabs: bool,
+    tab_width: usize,
+    max_paste_chars: Option<usize>,
+    dropped_paste_chars: usize,
+    strip_indent_on_join: bool,
+    auto_pairs: Vec<(char, char)>,
+    drag_anchor: Option<Pos>,
+    // edits (local and external ones as well) keep it on the same logical spot
+    mark: Option<Pos>,
+    insert_mode: bool,
+    virtual_space_max: Option<usize>,
+    max_line_count: Option<usize>,
+    ctrl_del_crosses_line: bool,
+    single_line: bool,
+    // how many rows PageUp/PageDown move the cursor
+    page_size: usize,
+    scroll_top: usize,
+    // how many rows the host can show, 0 if it is not known
+    viewport_height: usize,
     pub clipboard: String,
 }
 
@@ -312,12 +495,39 @@ impl Editor {
             next_blink_at: 0,
             modif_time_treshold_expires_at: 0,
             show_cursor: false,
+            wide_char_aware_click: false,
+            last_input_effect: InputEffect::NoChange,
+            wrap_width: None,
+            force_soft_tabs: true,
+            tab_width: 4,
+            max_paste_chars: None,
+            dropped_paste_chars: 0,
+            strip_indent_on_join: false,
+            drag_anchor: None,
+            mark: None,
+            insert_mode: true,
+            virtual_space_max: None,
+            max_line_count: None,
+            ctrl_del_crosses_line: false,
+            single_line: false,
+            page_size: 20,
+            scroll_top: 0,
+            viewport_height: 0,
+            auto_pairs: vec![('(', ')'), ('[', ']'), ('{', '}'), ('\"', '\"')],
             clipboard: String::new(),
         };
         content.push_line();
         return ed;
     }
 
+    pub fn clear_to_single_line<T: Default + Clone + Debug>(
+        &mut self,
+        content: &mut EditorContent<T>,
+    ) {
+        content.clear_to_single_line();
+        self.set_cursor_pos_r_c(0, 0);
+    }
+
     pub fn is_cursor_at_eol<T: Default + Clone + Debug>(&self, content: &EditorContent<T>) -> bool {
         let cur_pos = self.selection.get_cursor_pos();
         cur_pos.column == content.line_len(cur_pos.row)
@@ -343,17 +553,345 @@ impl Editor {
         self.selection
     }
 
-    pub fn handle_click<T: Default + Clone + Debug>(
+    /// vim's f/F/t/T motions. With `till` an occurrence right next to the cursor is skipped
+    /// (like vim's `;`), otherwise repeating the motion would never move the cursor.
+    pub fn find_char_in_line<T: Default + Clone + Debug>(
+        &mut self,
+        ch: char,
+        forward: bool,
+        till: bool,
+        extend_selection: bool,
+        content: &EditorContent<T>,
+    ) -> bool {
+        let cur_pos = self.selection.get_cursor_pos();
+        let line = content.get_line_valid_chars(cur_pos.row);
+        let skipped = if till { 1 } else { 0 };
+        let found_col = if forward {
+            line.iter()
+                .enumerate()
+                .skip(cur_pos.column + 1 + skipped)
+                .find(|(_i, it)| **it == ch)
+                .map(|(i, _it)| if till { i - 1 } else { i })
+        } else {
+            line[0..cur_pos.column.min(line.len()).saturating_sub(skipped)]
+                .iter()
+                .rposition(|it| *it == ch)
+                .map(|i| if till { i + 1 } else { i })
+        };
+        let new_pos = match found_col {
+            Some(col) => cur_pos.with_column(col),
+            None => return false,
+        };
+        self.set_selection_save_col(if extend_selection {
+            self.selection.extend(new_pos)
+        } else {
+            Selection::single(new_pos)
+        });
+        true
+    }
+
+    /// Grows the selection so it covers whole lines: from the beginning of its first row
+    /// to the beginning of the row after its last one (or to the end of the last row).
+    pub fn expand_selection_to_full_lines<T: Default + Clone + Debug>(
         &mut self,
+        content: &EditorContent<T>,
+    ) {
+        let first_row = self.selection.get_first().row;
+        let last_row = self.selection.get_second().row;
+        let end = if last_row + 1 < content.line_count() {
+            Pos::from_row_column(last_row + 1, 0)
+        } else {
+            Pos::from_row_column(last_row, content.line_len(last_row))
+        };
+        self.set_selection_save_col(Selection::range(Pos::from_row_column(first_row, 0), end));
+    }
+
+    pub fn selection_covering(&self, positions: &[Pos]) -> Option<Selection> {
+        let first = positions.iter().min_by_key(|pos| (pos.row, pos.column))?;
+        let last = positions.iter().max_by_key(|pos| (pos.row, pos.column))?;
+        Some(Selection::range(*first, *last))
+    }
+
+    pub fn editor_cursor_state(&self) -> CursorState {
+        let cursor = self.selection.get_cursor_pos();
+        let (start, end) = self.selection.get_range();
+        CursorState {
+            row: cursor.row,
+            column: cursor.column,
+            sel_start_row: start.row,
+            sel_start_col: start.column,
+            sel_end_row: end.row,
+            sel_end_col: end.column,
+            has_selection: self.selection.is_range(),
+        }
+    }
+
+    pub fn scroll_top(&self) -> usize {
+        self.scroll_top
+    }
+
+    pub fn set_scroll_top(&mut self, scroll_top: usize) {
+        self.scroll_top = scroll_top;
+    }
+
+    pub fn set_viewport_height(&mut self, viewport_height: usize) {
+        self.viewport_height = viewport_height;
+    }
+
+    /// If the whole selection does not fit, only its cursor side is brought into view.
+    pub fn scroll_selection_into_view(&mut self) {
+        let scroll_top = self.scroll_top;
+        let viewport_height = self.viewport_height;
+        if viewport_height == 0 {
+            return;
+        }
+        let first_row = self.selection.get_first().row;
+        let last_row = self.selection.get_second().row;
+        let (first_row, last_row) = if last_row - first_row < viewport_height {
+            (first_row, last_row)
+        } else {
+            let cursor_row = self.selection.get_cursor_pos().row;
+            (cursor_row, cursor_row)
+        };
+        if first_row < scroll_top {
+            self.scroll_top = first_row;
+        } else if last_row >= scroll_top + viewport_height {
+            self.scroll_top = last_row + 1 - viewport_height;
+        }
+    }
+
+    pub fn view_state(&self) -> ViewState {
+        ViewState {
+            selection: self.selection,
+            last_column_index: self.last_column_index,
+            mark: self.mark,
+            scroll_top: self.scroll_top,
+        }
+    }
+
+    pub fn restore_view_state<T: Default + Clone + Debug>(
+        &mut self,
+        state: ViewState,
+        content: &EditorContent<T>,
+    ) {
+        self.selection = self.clamp_selection(state.selection, content);
+        self.last_column_index = state.last_column_index;
+        self.mark = state
+            .mark
+            .map(|mark| self.clamp_selection(Selection::single(mark), content).start);
+        self.scroll_top = state.scroll_top.min(content.line_count() - 1);
+    }
+
+    pub fn clamp_selection<T: Default + Clone + Debug>(
+        &self,
+        selection: Selection,
+        content: &EditorContent<T>,
+    ) -> Selection {
+        let clamp = |pos: Pos| {
+            let row = pos.row.min(content.line_count() - 1);
+            Pos::from_row_column(row, pos.column.min(content.line_len(row)))
+        };
+        let start = clamp(selection.start);
+        let end = clamp(selection.end.unwrap_or(selection.start));
+        Selection::range(start, end)
+    }
+
+    pub fn selection_is_forward(&self) -> Option<bool> {
+        let (start, end) = self.selection.is_range_ordered()?;
+        Some(start == self.selection.start && end == self.selection.get_cursor_pos())
+    }
+
+    pub fn normalized_selection(&self) -> Selection {
+        Selection::range(self.selection.get_first(), self.selection.get_second())
+    }
+
+    /// Tab always inserts spaces inside the leading whitespace of a line.
+    /// Elsewhere it inserts spaces only if soft tabs are forced (default),
+    /// otherwise a literal tab character.
+    pub fn set_force_soft_tabs(&mut self, force_soft_tabs: bool) {
+        self.force_soft_tabs = force_soft_tabs;
+    }
+
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.tab_width = tab_width.max(1);
+    }
+
+    /// Only Editor::paste and paste_matching_indent are limited, other insertions are not.
+    pub fn set_max_paste_chars(&mut self, max_paste_chars: Option<usize>) {
+        self.max_paste_chars = max_paste_chars;
+    }
+
+    pub fn dropped_paste_chars(&self) -> usize {
+        self.dropped_paste_chars
+    }
+
+    /// If set, Ctrl-Del at the end of a line does not only merge the next line,
+    /// but also deletes the whitespaces and the first word of it.
+    pub fn set_ctrl_del_crosses_line(&mut self, ctrl_del_crosses_line: bool) {
+        self.ctrl_del_crosses_line = ctrl_del_crosses_line;
+    }
+
+    pub fn set_strip_indent_on_join(&mut self, strip_indent_on_join: bool) {
+        self.strip_indent_on_join = strip_indent_on_join;
+    }
+
+    pub fn set_insert_mode(&mut self, insert_mode: bool) {
+        self.insert_mode = insert_mode;
+    }
+
+    pub fn is_insert_mode(&self) -> bool {
+        self.insert_mode
+    }
+
+    /// With virtual space the cursor can be placed after the end of a line, up to
+    /// `max` columns. Typing there fills the gap with spaces. None disables it.
+    pub fn set_virtual_space(&mut self, max: Option<usize>) {
+        self.virtual_space_max = max;
+    }
+
+    pub fn max_column<T: Default + Clone + Debug>(
+        &self,
+        row: usize,
+        content: &EditorContent<T>,
+    ) -> usize {
+        let line_len = content.line_len(row);
+        match self.virtual_space_max {
+            Some(max) => max.min(content.max_line_len()).max(line_len),
+            None => line_len,
+        }
+    }
+
+    pub fn set_max_line_count(&mut self, max: Option<usize>) {
+        self.max_line_count = max;
+    }
+
+    /// In single line mode (e.g. for a search box) Enter does not insert a line break
+    /// but sets the last input effect to InputEffect::Submit, and line breaks
+    /// in inserted texts are replaced by spaces.
+    pub fn set_single_line(&mut self, single_line: bool) {
+        self.single_line = single_line;
+    }
+
+    pub fn set_page_size(&mut self, page_size: usize) {
+        self.page_size = page_size.max(1);
+    }
+
+    fn would_exceed_max_line_count<T: Default + Clone + Debug>(
+        &self,
+        input: EditorInputEvent,
+        modifiers: InputModifiers,
+        content: &EditorContent<T>,
+    ) -> bool {
+        let max = match self.max_line_count {
+            Some(max) => max,
+            None => return false,
+        };
+        // a multiline selection is replaced by the new line, so the line count can't grow,
+        // but Ctrl-Shift-Enter inserts an empty row and keeps the selection
+        let adds_line = input == EditorInputEvent::Enter
+            && (modifiers.is_ctrl_shift()
+                || self.selection.get_first().row == self.selection.get_second().row);
+        adds_line && content.line_count() >= max
+    }
+
+    pub fn set_auto_pairs(&mut self, pairs: &[(char, char)]) {
+        self.auto_pairs.clear();
+        self.auto_pairs.extend_from_slice(pairs);
+    }
+
+    pub fn auto_pair_closing_for(&self, opening: char) -> Option<char> {
+        self.auto_pairs
+            .iter()
+            .find(|(o, _c)| *o == opening)
+            .map(|(_o, c)| *c)
+    }
+
+    pub fn is_auto_pair_closing(&self, ch: char) -> bool {
+        self.auto_pairs.iter().any(|(o, c)| *c == ch && *o != ch)
+    }
+
+    pub fn wrap_width(&self) -> Option<usize> {
+        self.wrap_width
+    }
+
+    /// Turns soft-wrap on (with the given width) or off.
+    /// The logical cursor position does not change, only the remembered column for Up/Down
+    /// is reset to the current one.
+    pub fn set_wrap(&mut self, width: Option<usize>) {
+        self.wrap_width = width;
+        self.last_column_index = self.selection.get_cursor_pos().column;
+    }
+
+    pub fn display_row_count<T: Default + Clone + Debug>(
+        &self,
+        content: &EditorContent<T>,
+    ) -> usize {
+        let width = match self.wrap_width {
+            Some(width) if width > 0 => width,
+            _ => return content.line_count(),
+        };
+        let mut count = 0;
+        for row in 0..content.line_count() {
+            let len = content.line_len(row);
+            let mut col = 0;
+            count += 1;
+            loop {
+                col += content.wrap_segment_at(row, col, width);
+                if col >= len {
+                    break;
+                }
+                count += 1;
+            }
+        }
+        count
+    }
+
+    pub fn last_input_effect(&self) -> InputEffect {
+        self.last_input_effect
+    }
+
+    /// If set, the x coordinate of clicks and drags is treated as a display column,
+    /// and clicking into a wide char snaps to its nearest edge.
+    pub fn set_wide_char_aware_click(&mut self, enabled: bool) {
+        self.wide_char_aware_click = enabled;
+    }
+
+    pub fn resolve_click<T: Default + Clone + Debug>(
+        &self,
         x: usize,
         y: usize,
         content: &EditorContent<T>,
-    ) {
+    ) -> Pos {
         let line_count = content.line_count();
         let y = if y >= line_count { line_count - 1 } else { y };
 
-        let col = x.min(content.line_len(y));
-        self.set_cursor_pos_r_c(y, col);
+        let col = if self.wide_char_aware_click {
+            let line_width = content.line_display_width(y);
+            if x > line_width {
+                // every cell after the end of the line is a column of virtual space
+                (content.line_len(y) + (x - line_width)).min(self.max_column(y, content))
+            } else {
+                content.display_col_to_char_col(y, x)
+            }
+        } else {
+            x.min(self.max_column(y, content))
+        };
+        Pos::from_row_column(y, col)
+    }
+
+    pub fn handle_click<T: Default + Clone + Debug>(
+        &mut self,
+        x: usize,
+        y: usize,
+        content: &EditorContent<T>,
+    ) {
+        let pos = self.resolve_click(x, y, content);
+        self.drag_anchor = Some(pos);
+        self.set_cursor_pos(pos);
+    }
+
+    pub fn handle_mouse_up(&mut self) {
+        self.drag_anchor = None;
     }
 
     pub fn handle_drag<T: Default + Clone + Debug>(
@@ -362,86 +900,858 @@ impl Editor {
         y: usize,
         content: &EditorContent<T>,
     ) {
-        let y = if y >= content.line_count() {
-            content.line_count() - 1
-        } else {
-            y
-        };
-        let col = x.min(content.line_len(y));
-        self.set_selection_save_col(self.selection.extend(Pos::from_row_column(y, col)));
+        self.handle_drag_with_mode(x, y, DragMode::Char, content);
     }
 
-    pub fn get_selected_text_single_line<T: Default + Clone + Debug>(
-        selection: Selection,
+    /// In Word and Line mode both the anchor (the last clicked position) and the dragged end
+    /// are snapped to the enclosing word or line, so the anchor word/line stays fully
+    /// selected even when dragging backwards.
+    pub fn handle_drag_with_mode<T: Default + Clone + Debug>(
+        &mut self,
+        x: usize,
+        y: usize,
+        mode: DragMode,
         content: &EditorContent<T>,
-    ) -> Option<&[char]> {
-        return if selection.end.is_none() || selection.start.row != selection.end.unwrap().row {
-            None
+    ) {
+        let pos = self.resolve_click(x, y, content);
+        let anchor = self.drag_anchor.unwrap_or(self.selection.start);
+        let (anchor_start, anchor_end, target_start, target_end) = match mode {
+            DragMode::Char => {
+                self.set_selection_save_col(self.selection.extend(pos));
+                return;
+            }
+            DragMode::Word => {
+                let (anchor_start, anchor_end) = Editor::word_range_at(anchor, content);
+                let (target_start, target_end) = Editor::word_range_at(pos, content);
+                (anchor_start, anchor_end, target_start, target_end)
+            }
+            DragMode::Line => (
+                anchor.with_column(0),
+                anchor.with_column(content.line_len(anchor.row)),
+                pos.with_column(0),
+                pos.with_column(content.line_len(pos.row)),
+            ),
+        };
+        let selection = if (pos.row, pos.column) < (anchor_start.row, anchor_start.column) {
+            Selection::range(anchor_end, target_start)
+        } else if (target_end.row, target_end.column) < (anchor_end.row, anchor_end.column) {
+            Selection::range(anchor_start, anchor_end)
         } else {
-            let start = selection.get_first();
-            let end = selection.get_second();
-            Some(&content.get_line_valid_chars(start.row)[start.column..end.column])
+            Selection::range(anchor_start, target_end)
         };
+        self.set_selection_save_col(selection);
     }
 
-    pub fn clone_range<T: Default + Clone + Debug>(
-        start: Pos,
-        end: Pos,
-        content: &EditorContent<T>,
-    ) -> String {
-        let mut result = String::with_capacity((end.row - start.row) * content.max_line_len());
+    /// Moves the cursor to the beginning of the word under it (unlike Ctrl-Left,
+    /// which moves to the previous word if the cursor is already at a word start).
+    pub fn move_to_word_start<T: Default + Clone + Debug>(&mut self, content: &EditorContent<T>) {
+        let (start, _end) = Editor::word_range_at(self.selection.get_cursor_pos(), content);
+        self.set_selection_save_col(Selection::single(start));
+    }
 
-        content.write_selection_into(Selection::range(start, end), &mut result);
-        result
+    pub fn move_to_word_end<T: Default + Clone + Debug>(&mut self, content: &EditorContent<T>) {
+        let (_start, end) = Editor::word_range_at(self.selection.get_cursor_pos(), content);
+        self.set_selection_save_col(Selection::single(end));
     }
 
-    #[inline]
-    pub fn set_cursor_pos(&mut self, pos: Pos) {
-        self.set_selection_save_col(Selection::single(pos));
+    pub fn cursor_in_quotes<T: Default + Clone + Debug>(&self, content: &EditorContent<T>) -> bool {
+        let cursor_pos = self.selection.get_cursor_pos();
+        Editor::unescaped_quotes(cursor_pos.row..cursor_pos.row + 1, content)
+            .iter()
+            .filter(|pos| pos.column < cursor_pos.column)
+            .count()
+            % 2
+            == 1
     }
 
-    #[inline]
-    pub fn set_cursor_pos_r_c(&mut self, row_index: usize, column_index: usize) {
-        self.set_selection_save_col(Selection::single_r_c(row_index, column_index));
+    /// If `across_lines` is true, the string literal can span multiple lines and the quotes
+    /// are counted from the beginning of the content, otherwise only the cursor's line is scanned.
+    pub fn select_inside_quotes<T: Default + Clone + Debug>(
+        &mut self,
+        across_lines: bool,
+        content: &EditorContent<T>,
+    ) -> bool {
+        let cursor_pos = self.selection.get_cursor_pos();
+        let rows = if across_lines {
+            0..content.line_count()
+        } else {
+            cursor_pos.row..cursor_pos.row + 1
+        };
+        let quotes = Editor::unescaped_quotes(rows, content);
+        let before_count = quotes
+            .iter()
+            .take_while(|pos| (pos.row, pos.column) < (cursor_pos.row, cursor_pos.column))
+            .count();
+        if before_count % 2 == 0 || before_count == quotes.len() {
+            return false;
+        }
+        let opening = quotes[before_count - 1];
+        let closing = quotes[before_count];
+        self.set_selection_save_col(Selection::range(opening.with_next_col(), closing));
+        true
     }
 
-    #[inline]
-    pub fn set_cursor_range(&mut self, start: Pos, end: Pos) {
-        self.set_selection_save_col(Selection::range(start, end));
+    fn unescaped_quotes<T: Default + Clone + Debug>(
+        rows: Range<usize>,
+        content: &EditorContent<T>,
+    ) -> Vec<Pos> {
+        let mut result = Vec::new();
+        for row in rows {
+            let mut escaped = false;
+            for (column, ch) in content.get_line_valid_chars(row).iter().enumerate() {
+                if escaped {
+                    escaped = false;
+                } else if *ch == '\\' {
+                    escaped = true;
+                } else if *ch == '"' {
+                    result.push(Pos::from_row_column(row, column));
+                }
+            }
+        }
+        result
     }
 
-    #[inline]
-    pub fn set_selection_save_col(&mut self, selection: Selection) {
-        self.selection = selection;
-        self.last_column_index = selection.get_cursor_pos().column;
-        debug_assert!(self.last_column_index <= 120, "{}", self.last_column_index);
+    fn word_range_at<T: Default + Clone + Debug>(
+        pos: Pos,
+        content: &EditorContent<T>,
+    ) -> (Pos, Pos) {
+        let start = content.jump_word_backward(&pos, JumpMode::BlockOnWhitespace);
+        let end = content.jump_word_forward(&pos, JumpMode::BlockOnWhitespace);
+        (pos.with_column(start), pos.with_column(end))
     }
 
-    pub fn is_cursor_shown(&self) -> bool {
-        self.show_cursor
+    pub fn transpose_lines_no_undo<T: Default + Clone + Debug>(
+        &mut self,
+        content: &mut EditorContent<T>,
+    ) -> Option<RowModificationType> {
+        self.transpose_lines(content, false)
     }
 
-    pub fn blink_cursor(&mut self) {
-        self.show_cursor = true;
-        self.next_blink_at = self.time + EDITOR_CURSOR_TICK_MS;
+    pub fn transpose_lines_undoable<T: Default + Clone + Debug>(
+        &mut self,
+        content: &mut EditorContent<T>,
+    ) -> Option<RowModificationType> {
+        self.transpose_lines(content, true)
     }
 
-    pub fn handle_tick(&mut self, now: u32) -> bool {
-        self.time = now;
-        return if now >= self.next_blink_at {
-            self.show_cursor = !self.show_cursor;
-            self.next_blink_at = now + EDITOR_CURSOR_TICK_MS;
-            true
+    /// emacs C-x C-t, if the selection spans exactly two lines, those two lines are swapped instead.
+    fn transpose_lines<T: Default + Clone + Debug>(
+        &mut self,
+        content: &mut EditorContent<T>,
+        undoable: bool,
+    ) -> Option<RowModificationType> {
+        let first = self.selection.get_first();
+        let second = self.selection.get_second();
+        if self.selection.is_range() && second.row == first.row + 1 {
+            let modif_type = self.execute_user_input(
+                EditorCommand::SwapLineUpwards(second.with_column(0)),
+                content,
+                undoable,
+            );
+            self.set_selection_save_col(Selection::range(
+                first.with_column(0),
+                second.with_column(content.line_len(second.row)),
+            ));
+            modif_type
         } else {
-            false
-        };
+            let cur_pos = self.selection.get_cursor_pos();
+            if cur_pos.row == 0 {
+                return None;
+            }
+            let modif_type = self.execute_user_input(
+                EditorCommand::SwapLineUpwards(cur_pos.with_column(0)),
+                content,
+                undoable,
+            );
+            let new_row = (cur_pos.row + 1).min(content.line_count() - 1);
+            self.set_cursor_pos_r_c(new_row, cur_pos.column.min(content.line_len(new_row)));
+            modif_type
+        }
     }
 
-    fn create_command<T: Default + Clone + Debug>(
-        &self,
-        input: &EditorInputEvent,
-        modifiers: InputModifiers,
-        content: &EditorContent<T>,
+    pub fn move_block_no_undo<T: Default + Clone + Debug>(
+        &mut self,
+        rows: Range<usize>,
+        direction: i32,
+        content: &mut EditorContent<T>,
+    ) -> Option<RowModificationType> {
+        self.move_block(rows, direction, content, false)
+    }
+
+    pub fn move_block_undoable<T: Default + Clone + Debug>(
+        &mut self,
+        rows: Range<usize>,
+        direction: i32,
+        content: &mut EditorContent<T>,
+    ) -> Option<RowModificationType> {
+        self.move_block(rows, direction, content, true)
+    }
+
+    /// Moves the rows past the `direction.abs()` rows below (positive direction)
+    /// or above (negative direction) them, preserving their order.
+    /// The movement is clamped at the document boundaries.
+    fn move_block<T: Default + Clone + Debug>(
+        &mut self,
+        rows: Range<usize>,
+        direction: i32,
+        content: &mut EditorContent<T>,
+        undoable: bool,
+    ) -> Option<RowModificationType> {
+        let line_count = content.line_count();
+        if rows.start >= rows.end || rows.end > line_count {
+            return None;
+        }
+        let distance = (direction as i64).abs() as usize;
+        let distance = if direction > 0 {
+            distance.min(line_count - rows.end)
+        } else {
+            distance.min(rows.start)
+        };
+        if distance == 0 {
+            return None;
+        }
+        let block_len = rows.end - rows.start;
+        let moving_down = direction > 0;
+        let map_row = |row: usize| -> usize {
+            if rows.contains(&row) {
+                if moving_down {
+                    row + distance
+                } else {
+                    row - distance
+                }
+            } else if moving_down && row >= rows.end && row < rows.end + distance {
+                row - block_len
+            } else if !moving_down && row < rows.start && row >= rows.start - distance {
+                row + block_len
+            } else {
+                row
+            }
+        };
+        let original_selection = self.selection;
+
+        for k in 0..distance {
+            if moving_down {
+                // the line below the block bubbles up above it
+                for lower_row in (rows.start + k + 1..=rows.end + k).rev() {
+                    let pos = Pos::from_row_column(lower_row, 0);
+                    self.execute_user_input(EditorCommand::SwapLineUpwards(pos), content, undoable);
+                }
+            } else {
+                // the line above the block bubbles down below it
+                for lower_row in rows.start - k..rows.end - k {
+                    let pos = Pos::from_row_column(lower_row, 0);
+                    self.execute_user_input(EditorCommand::SwapLineUpwards(pos), content, undoable);
+                }
+            }
+        }
+
+        // a selection end at the start of the row after the block stays right after the block
+        let map_pos = |pos: Pos| -> Pos {
+            if pos.column == 0 && pos.row == rows.end && pos != original_selection.get_first() {
+                pos.with_row(map_row(rows.end - 1) + 1)
+            } else {
+                pos.with_row(map_row(pos.row))
+            }
+        };
+        let (start, end) = original_selection.get_range();
+        self.set_selection_save_col(Selection::range(map_pos(start), map_pos(end)));
+        Some(RowModificationType::AllLinesFrom(if moving_down {
+            rows.start
+        } else {
+            rows.start - distance
+        }))
+    }
+
+    pub fn swap_rows<T: Default + Clone + Debug>(
+        &mut self,
+        a: usize,
+        b: usize,
+        content: &mut EditorContent<T>,
+    ) -> Option<RowModificationType> {
+        if a == b || a >= content.line_count() || b >= content.line_count() {
+            return None;
+        }
+        self.execute_user_input(EditorCommand::SwapRows { a, b }, content, true)
+    }
+
+    fn shift_rows_in_selection(&mut self, from_row: usize, delta: isize) {
+        let map_row = |pos: Pos| {
+            if pos.row >= from_row {
+                pos.with_row((pos.row as isize + delta) as usize)
+            } else {
+                pos
+            }
+        };
+        let (start, end) = self.selection.get_range();
+        self.set_selection_save_col(Selection::range(map_row(start), map_row(end)));
+    }
+
+    fn swap_rows_in_selection(&mut self, a: usize, b: usize) {
+        let map_row = |pos: Pos| {
+            if pos.row == a {
+                pos.with_row(b)
+            } else if pos.row == b {
+                pos.with_row(a)
+            } else {
+                pos
+            }
+        };
+        let (start, end) = self.selection.get_range();
+        self.set_selection_save_col(Selection::range(map_row(start), map_row(end)));
+    }
+
+    pub fn delete_to<T: Default + Clone + Debug>(
+        &mut self,
+        target: Pos,
+        content: &mut EditorContent<T>,
+    ) -> Option<RowModificationType> {
+        let row = target.row.min(content.line_count() - 1);
+        let target = Pos::from_row_column(row, target.column.min(content.line_len(row)));
+        let selection = Selection::range(self.selection.get_cursor_pos(), target);
+        if let Some((start, end)) = selection.is_range_ordered() {
+            let command = EditorCommand::DelSelection {
+                removed_text: Editor::clone_range(start, end, content),
+                selection,
+            };
+            self.execute_user_input(command, content, true)
+        } else {
+            None
+        }
+    }
+
+    pub fn select_to_matching_bracket<T: Default + Clone + Debug>(
+        &mut self,
+        content: &EditorContent<T>,
+    ) -> bool {
+        let cur_pos = self.selection.get_cursor_pos();
+        let bracket_pos = content
+            .matching_bracket(cur_pos)
+            .map(|matching| (cur_pos, matching))
+            .or_else(|| {
+                if cur_pos.column == 0 {
+                    return None;
+                }
+                let before = cur_pos.with_prev_col();
+                content
+                    .matching_bracket(before)
+                    .map(|matching| (before, matching))
+            });
+        let (bracket, matching) = match bracket_pos {
+            Some(it) => it,
+            None => return false,
+        };
+        let selection = if (matching.row, matching.column) > (bracket.row, bracket.column) {
+            Selection::range(bracket, matching.with_next_col())
+        } else {
+            Selection::range(bracket.with_next_col(), matching)
+        };
+        self.set_selection_save_col(selection);
+        true
+    }
+
+    /// Forces a visual line break at the cursor without splitting the logical line,
+    /// see EditorContent::soft_breaks. Returns false if a break can't be placed there.
+    pub fn insert_soft_break<T: Default + Clone + Debug>(
+        &mut self,
+        content: &mut EditorContent<T>,
+    ) -> bool {
+        if self.selection.is_range() {
+            return false;
+        }
+        content.add_soft_break(self.selection.get_cursor_pos())
+    }
+
+    pub fn toggle_bookmark<T: Default + Clone + Debug>(&mut self, content: &mut EditorContent<T>) {
+        content.toggle_bookmark(self.selection.get_cursor_pos().row);
+    }
+
+    pub fn next_bookmark<T: Default + Clone + Debug>(
+        &mut self,
+        content: &EditorContent<T>,
+    ) -> bool {
+        let cur_row = self.selection.get_cursor_pos().row;
+        let bookmarks = content.bookmarks();
+        let target = bookmarks
+            .iter()
+            .find(|row| **row > cur_row)
+            .or_else(|| bookmarks.first());
+        self.jump_to_bookmark(target.copied())
+    }
+
+    pub fn prev_bookmark<T: Default + Clone + Debug>(
+        &mut self,
+        content: &EditorContent<T>,
+    ) -> bool {
+        let cur_row = self.selection.get_cursor_pos().row;
+        let bookmarks = content.bookmarks();
+        let target = bookmarks
+            .iter()
+            .rev()
+            .find(|row| **row < cur_row)
+            .or_else(|| bookmarks.last());
+        self.jump_to_bookmark(target.copied())
+    }
+
+    fn jump_to_bookmark(&mut self, row: Option<usize>) -> bool {
+        if let Some(row) = row {
+            self.set_selection_save_col(Selection::single_r_c(row, 0));
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn set_mark(&mut self) {
+        self.mark = Some(self.selection.get_cursor_pos());
+    }
+
+    pub fn mark(&self) -> Option<Pos> {
+        self.mark
+    }
+
+    pub fn select_to_mark<T: Default + Clone + Debug>(
+        &mut self,
+        content: &EditorContent<T>,
+    ) -> bool {
+        if let Some(mark) = self.mark {
+            let cursor_pos = self.selection.get_cursor_pos();
+            let selection = self.clamp_selection(Selection::range(mark, cursor_pos), content);
+            self.set_selection_save_col(selection);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn tidy_current_line_no_undo<T: Default + Clone + Debug>(
+        &mut self,
+        content: &mut EditorContent<T>,
+    ) -> Option<RowModificationType> {
+        self.tidy_current_line(content, false)
+    }
+
+    pub fn tidy_current_line_undoable<T: Default + Clone + Debug>(
+        &mut self,
+        content: &mut EditorContent<T>,
+    ) -> Option<RowModificationType> {
+        self.tidy_current_line(content, true)
+    }
+
+    fn tidy_current_line<T: Default + Clone + Debug>(
+        &mut self,
+        content: &mut EditorContent<T>,
+        undoable: bool,
+    ) -> Option<RowModificationType> {
+        let cur_pos = self.selection.get_cursor_pos();
+        let line = content.get_line_valid_chars(cur_pos.row);
+        let trimmed_len = line.len()
+            - line
+                .iter()
+                .rev()
+                .take_while(|ch| ch.is_whitespace())
+                .count();
+        let selection = if trimmed_len > 0 {
+            Selection::range(
+                cur_pos.with_column(trimmed_len),
+                cur_pos.with_column(line.len()),
+            )
+        } else if cur_pos.row + 1 < content.line_count() {
+            Selection::range(
+                cur_pos.with_column(0),
+                Pos::from_row_column(cur_pos.row + 1, 0),
+            )
+        } else if cur_pos.row > 0 {
+            Selection::range(
+                Pos::from_row_column(cur_pos.row - 1, content.line_len(cur_pos.row - 1)),
+                cur_pos.with_column(line.len()),
+            )
+        } else {
+            Selection::range(cur_pos.with_column(0), cur_pos.with_column(line.len()))
+        };
+        let (start, end) = selection.is_range_ordered()?;
+        let modif_type = self.execute_user_input(
+            EditorCommand::DelSelection {
+                removed_text: Editor::clone_range(start, end, content),
+                selection,
+            },
+            content,
+            undoable,
+        );
+        if modif_type.is_some() && trimmed_len > 0 {
+            self.set_selection_save_col(Selection::single(
+                cur_pos.with_column(cur_pos.column.min(trimmed_len)),
+            ));
+        }
+        modif_type
+    }
+
+    pub fn selection_summary<T: Default + Clone + Debug>(
+        &self,
+        content: &EditorContent<T>,
+    ) -> Option<SelectionSummary> {
+        let (first, second) = self.selection.is_range_ordered()?;
+        let mut char_count = 0;
+        for row in first.row..=second.row {
+            let from = if row == first.row { first.column } else { 0 };
+            let to = if row == second.row {
+                second.column
+            } else {
+                // +1 for the line break
+                content.line_len(row) + 1
+            };
+            char_count += to - from;
+        }
+        Some(SelectionSummary {
+            line_count: second.row - first.row + 1,
+            char_count,
+            is_multiline: second.row > first.row,
+        })
+    }
+
+    /// If `block` is set, the selection is treated as the rectangle between its two corners,
+    /// clamped to the length of each row.
+    pub fn selection_per_line<T: Default + Clone + Debug>(
+        &self,
+        block: bool,
+        content: &EditorContent<T>,
+    ) -> Vec<(usize, usize, usize)> {
+        let first = self.selection.get_first();
+        let second = self.selection.get_second();
+        let from_col = first.column.min(second.column);
+        let to_col = first.column.max(second.column);
+        (first.row..=second.row)
+            .map(|row| {
+                let len = content.line_len(row);
+                if block {
+                    (row, from_col.min(len), to_col.min(len))
+                } else {
+                    let start = if row == first.row { first.column } else { 0 };
+                    let end = if row == second.row {
+                        second.column
+                    } else {
+                        len
+                    };
+                    (row, start, end)
+                }
+            })
+            .collect()
+    }
+
+    pub fn block_selection_widths<T: Default + Clone + Debug>(
+        &self,
+        content: &EditorContent<T>,
+    ) -> Option<Vec<usize>> {
+        if !self.selection.is_range() {
+            return None;
+        }
+        Some(
+            self.selection_per_line(true, content)
+                .into_iter()
+                .map(|(_row, from, to)| to - from)
+                .collect(),
+        )
+    }
+
+    pub fn get_block_text<T: Default + Clone + Debug>(
+        &self,
+        content: &EditorContent<T>,
+    ) -> Option<String> {
+        let (first, second) = self.selection.is_range_ordered()?;
+        let from_col = first.column.min(second.column);
+        let to_col = first.column.max(second.column);
+        let mut result = String::with_capacity((second.row - first.row + 1) * (to_col - from_col));
+        for row in first.row..=second.row {
+            if row > first.row {
+                result.push('\n');
+            }
+            result.push_str(&content.line_slice(row, from_col..to_col));
+        }
+        Some(result)
+    }
+
+    pub fn selection_as_tsv<T: Default + Clone + Debug>(
+        &self,
+        content: &EditorContent<T>,
+    ) -> Option<String> {
+        let (first, second) = self.selection.is_range_ordered()?;
+        let from_col = first.column.min(second.column);
+        let to_col = first.column.max(second.column);
+        let mut result = String::with_capacity((second.row - first.row + 1) * (to_col - from_col));
+        for row in first.row..=second.row {
+            if row > first.row {
+                result.push('\n');
+            }
+            let cells = content.line_slice(row, from_col..to_col);
+            for (i, cell) in cells.split('\t').enumerate() {
+                if i > 0 {
+                    result.push('\t');
+                }
+                result.push_str(cell.trim_end_matches(' '));
+            }
+        }
+        Some(result)
+    }
+
+    pub fn paste_tsv<T: Default + Clone + Debug>(
+        &mut self,
+        tsv: &str,
+        content: &mut EditorContent<T>,
+    ) -> Option<RowModificationType> {
+        let start = self.selection.get_first();
+        let mut modif_type: Option<RowModificationType> = None;
+        for (i, line) in tsv.lines().enumerate() {
+            let row = start.row + i;
+            let mut text = String::with_capacity(line.len() + start.column);
+            if row >= content.line_count() {
+                let last_row = content.line_count() - 1;
+                self.set_cursor_pos_r_c(last_row, content.line_len(last_row));
+                text.push('\n');
+            } else {
+                self.set_cursor_pos_r_c(row, start.column.min(content.line_len(row)));
+            }
+            let line_len = if row < content.line_count() {
+                content.line_len(row)
+            } else {
+                0
+            };
+            for _ in line_len..start.column {
+                text.push(' ');
+            }
+            text.push_str(line);
+            let row_modif_type = self.insert_text_undoable(&text, content);
+            if let Some(modif_type) = &mut modif_type {
+                modif_type.merge(row_modif_type.as_ref());
+            } else {
+                modif_type = row_modif_type;
+            }
+        }
+        modif_type
+    }
+
+    pub fn select_all<T: Default + Clone + Debug>(&mut self, content: &EditorContent<T>) {
+        let last_row = content.line_count() - 1;
+        // Selection::range collapses if the document is empty
+        self.set_selection_save_col(Selection::range(
+            Pos::from_row_column(0, 0),
+            Pos::from_row_column(last_row, content.line_len(last_row)),
+        ));
+    }
+
+    pub fn cut_selection<T: Default + Clone + Debug>(
+        &mut self,
+        content: &mut EditorContent<T>,
+    ) -> Option<String> {
+        self.cut(content, true).map(|(text, _modif_type)| text)
+    }
+
+    fn cut<T: Default + Clone + Debug>(
+        &mut self,
+        content: &mut EditorContent<T>,
+        undoable: bool,
+    ) -> Option<(String, Option<RowModificationType>)> {
+        let (start, end) = self.selection.is_range_ordered()?;
+        let text = Editor::clone_range(start, end, content);
+        let modif_type = self.execute_user_input(
+            EditorCommand::DelSelection {
+                selection: self.selection,
+                removed_text: text.clone(),
+            },
+            content,
+            undoable,
+        );
+        Some((text, modif_type))
+    }
+
+    pub fn get_selected_text<T: Default + Clone + Debug>(
+        &self,
+        content: &EditorContent<T>,
+    ) -> Option<String> {
+        if !self.selection.is_range() {
+            return None;
+        }
+        let mut result = String::with_capacity(64);
+        content.write_selection_into(self.selection, &mut result);
+        Some(result)
+    }
+
+    pub fn get_selected_lines_with_indices<T: Default + Clone + Debug>(
+        &self,
+        content: &EditorContent<T>,
+    ) -> Vec<(usize, String)> {
+        if !self.selection.is_range() {
+            return Vec::new();
+        }
+        self.selection_per_line(false, content)
+            .into_iter()
+            .map(|(row, start, end)| (row, content.line_slice(row, start..end)))
+            .collect()
+    }
+
+    pub fn get_selected_text_single_line<T: Default + Clone + Debug>(
+        selection: Selection,
+        content: &EditorContent<T>,
+    ) -> Option<&[char]> {
+        return if !selection.is_range() || selection.start.row != selection.end.unwrap().row {
+            None
+        } else {
+            let start = selection.get_first();
+            let end = selection.get_second();
+            Some(&content.get_line_valid_chars(start.row)[start.column..end.column])
+        };
+    }
+
+    pub fn clone_range<T: Default + Clone + Debug>(
+        start: Pos,
+        end: Pos,
+        content: &EditorContent<T>,
+    ) -> String {
+        let mut result = String::with_capacity((end.row - start.row) * content.max_line_len());
+
+        content.write_selection_into(Selection::range(start, end), &mut result);
+        result
+    }
+
+    #[inline]
+    pub fn set_cursor_pos(&mut self, pos: Pos) {
+        self.set_selection_save_col(Selection::single(pos));
+    }
+
+    #[inline]
+    pub fn set_cursor_pos_r_c(&mut self, row_index: usize, column_index: usize) {
+        self.set_selection_save_col(Selection::single_r_c(row_index, column_index));
+    }
+
+    #[inline]
+    pub fn set_cursor_range(&mut self, start: Pos, end: Pos) {
+        self.set_selection_save_col(Selection::range(start, end));
+    }
+
+    pub fn cursor_offset<T: Default + Clone + Debug>(&self, content: &EditorContent<T>) -> usize {
+        content.pos_to_offset(self.selection.get_cursor_pos())
+    }
+
+    pub fn set_cursor_offset<T: Default + Clone + Debug>(
+        &mut self,
+        offset: usize,
+        content: &EditorContent<T>,
+    ) {
+        self.set_cursor_pos(content.offset_to_pos(offset));
+    }
+
+    pub fn set_selection_by_offsets<T: Default + Clone + Debug>(
+        &mut self,
+        start: usize,
+        end: usize,
+        content: &EditorContent<T>,
+    ) {
+        let start = content.offset_to_pos(start);
+        let end = content.offset_to_pos(end);
+        self.set_selection_save_col(Selection::range(start, end));
+    }
+
+    /// Replaces the content (e.g. with its reformatted version) keeping the cursor
+    /// at the same char offset, clamped to the end of the new content.
+    /// Only the changed rows are replaced (and stored for undo), the others keep their data.
+    pub fn set_content_preserving_cursor<T: Default + Clone + Debug>(
+        &mut self,
+        text: &str,
+        content: &mut EditorContent<T>,
+    ) {
+        let offset = self.cursor_offset(content);
+        let mut new_content = EditorContent::<T>::new(content.max_line_len());
+        let mut new_editor = Editor::new(&mut new_content);
+        new_content.init_with(text);
+        new_editor.set_cursor_offset(offset, &new_content);
+
+        let snapshot = Editor::diff_snapshot(self, content, &new_editor, &new_content);
+        if snapshot.rows_before.is_empty()
+            && snapshot.rows_after.is_empty()
+            && snapshot.line_ending_before == snapshot.line_ending_after
+        {
+            self.set_selection_save_col(snapshot.selection_after);
+        } else {
+            self.execute_user_input(
+                EditorCommand::ReplaceRows(Box::new(snapshot)),
+                content,
+                true,
+            );
+        }
+    }
+
+    /// Creates the snapshot of the change between the two states, which stores only
+    /// the rows that differ.
+    pub(crate) fn diff_snapshot<T: Default + Clone + Debug>(
+        before: &Editor,
+        before_content: &EditorContent<T>,
+        after: &Editor,
+        after_content: &EditorContent<T>,
+    ) -> RowsSnapshot<T> {
+        let before_count = before_content.line_count();
+        let after_count = after_content.line_count();
+        let is_same_row = |before_row: usize, after_row: usize| {
+            before_content.get_line_valid_chars(before_row)
+                == after_content.get_line_valid_chars(after_row)
+        };
+        let common_prefix_len = (0..before_count.min(after_count))
+            .take_while(|row| is_same_row(*row, *row))
+            .count();
+        let common_suffix_len = (1..=before_count.min(after_count) - common_prefix_len)
+            .take_while(|i| is_same_row(before_count - i, after_count - i))
+            .count();
+        let rows = |content: &EditorContent<T>, line_count: usize| {
+            (common_prefix_len..line_count - common_suffix_len)
+                .map(|row| content.row_snapshot(row))
+                .collect()
+        };
+        RowsSnapshot {
+            first_row: common_prefix_len,
+            rows_before: rows(before_content, before_count),
+            rows_after: rows(after_content, after_count),
+            selection_before: before.selection,
+            selection_after: after.selection,
+            line_ending_before: before_content.detected_line_ending(),
+            line_ending_after: after_content.detected_line_ending(),
+        }
+    }
+
+    #[inline]
+    pub fn set_selection_save_col(&mut self, selection: Selection) {
+        let selection = selection.normalized();
+        self.selection = selection;
+        self.last_column_index = selection.get_cursor_pos().column;
+        debug_assert!(self.last_column_index <= 120, "{}", self.last_column_index);
+    }
+
+    pub fn is_cursor_shown(&self) -> bool {
+        self.show_cursor
+    }
+
+    pub fn blink_cursor(&mut self) {
+        self.show_cursor = true;
+        self.next_blink_at = self.time + EDITOR_CURSOR_TICK_MS;
+    }
+
+    pub fn reset_blink(&mut self) {
+        self.show_cursor = true;
+        self.next_blink_at = self.time + EDITOR_CURSOR_TICK_MS;
+    }
+
+    pub fn handle_tick(&mut self, now: u32) -> bool {
+        self.time = now;
+        return if now >= self.next_blink_at {
+            self.show_cursor = !self.show_cursor;
+            self.next_blink_at = now + EDITOR_CURSOR_TICK_MS;
+            true
+        } else {
+            false
+        };
+    }
+
+    fn create_command<T: Default + Clone + Debug>(
+        &self,
+        input: &EditorInputEvent,
+        modifiers: InputModifiers,
+        content: &EditorContent<T>,
     ) -> Option<EditorCommand<T>> {
         let selection = self.selection;
         let cur_pos = selection.get_cursor_pos();
@@ -451,8 +1761,34 @@ impl Editor {
             EditorInputEvent::PageUp => None,
             EditorInputEvent::PageDown => None,
             EditorInputEvent::Right => None,
+            EditorInputEvent::Tab if modifiers.shift && !selection.is_range() => {
+                let line = content.get_line_valid_chars(cur_pos.row);
+                let removed_len = self.outdent_len(line);
+                if removed_len == 0 {
+                    None
+                } else {
+                    Some(EditorCommand::OutdentLine {
+                        pos: cur_pos,
+                        removed_text: line[0..removed_len].iter().collect(),
+                    })
+                }
+            }
             EditorInputEvent::Tab => {
-                let target_pos = ((cur_pos.column / 4) + 1) * 4;
+                let in_leading_whitespace = content.get_line_valid_chars(cur_pos.row)
+                    [0..cur_pos.column]
+                    .iter()
+                    .all(|ch| ch.is_ascii_whitespace());
+                if !self.force_soft_tabs && !in_leading_whitespace {
+                    return if content.line_len(cur_pos.row) == content.max_line_len() {
+                        None
+                    } else {
+                        Some(EditorCommand::InsertChar {
+                            pos: cur_pos,
+                            ch: '\t',
+                        })
+                    };
+                }
+                let target_pos = ((cur_pos.column / self.tab_width) + 1) * self.tab_width;
                 let space_count = target_pos - cur_pos.column;
                 // TODO every tab is a string allocation :(
                 let str = std::iter::repeat(' ').take(space_count).collect::<String>();
@@ -531,8 +1867,10 @@ impl Editor {
                 }
             }
             EditorInputEvent::Enter => {
-                if modifiers.ctrl {
-                    Some(EditorCommand::InsertEmptyRow(cur_pos.row))
+                if modifiers.is_ctrl_shift() {
+                    Some(EditorCommand::InsertEmptyRow(selection.get_first().row))
+                } else if modifiers.ctrl {
+                    None
                 } else if let Some((start, end)) = selection.is_range_ordered() {
                     Some(EditorCommand::EnterSelection {
                         selection,
@@ -589,74 +1927,502 @@ impl Editor {
                     })
                 }
             }
-            EditorInputEvent::Char(ch) => {
-                if *ch == 'w' && modifiers.ctrl {
-                    None
-                } else if *ch == 'c' && modifiers.ctrl {
-                    None
-                } else if *ch == 'x' && modifiers.ctrl {
-                    if let Some((start, end)) = selection.is_range_ordered() {
-                        Some(EditorCommand::DelSelection {
-                            selection,
-                            removed_text: Editor::clone_range(start, end, content),
-                        })
-                    } else {
-                        Some(EditorCommand::CutLine {
-                            pos: cur_pos,
-                            removed_text: Editor::clone_range(
-                                cur_pos.with_column(0),
-                                cur_pos.with_column(content.line_len(cur_pos.row)),
-                                content,
-                            ),
-                        })
-                    }
-                } else if *ch == 'd' && modifiers.ctrl {
-                    Some(EditorCommand::DuplicateLine {
-                        pos: cur_pos,
-                        inserted_text: Editor::clone_range(
-                            cur_pos.with_column(0),
-                            cur_pos.with_column(content.line_len(cur_pos.row)),
-                            content,
-                        ),
-                    })
-                } else if *ch == 'a' && modifiers.ctrl {
-                    None
-                } else if ch.to_ascii_lowercase() == 'z' && modifiers.ctrl && modifiers.shift {
-                    None
-                } else if ch.to_ascii_lowercase() == 'z' && modifiers.ctrl {
-                    None
-                } else if let Some((start, end)) = selection.is_range_ordered() {
-                    Some(EditorCommand::InsertCharSelection {
-                        ch: *ch,
-                        selection,
-                        selected_text: Editor::clone_range(start, end, content),
-                    })
-                } else if content.line_len(cur_pos.row) == content.max_line_len() {
-                    None
+            EditorInputEvent::Char(ch) => {
+                if *ch == 'w' && modifiers.ctrl {
+                    None
+                } else if *ch == 'l' && modifiers.ctrl {
+                    None
+                } else if *ch == 'c' && modifiers.ctrl {
+                    None
+                } else if *ch == 'x' && modifiers.ctrl {
+                    // a selection is removed by Editor::cut
+                    let mut removed_text = Editor::clone_range(
+                        cur_pos.with_column(0),
+                        cur_pos.with_column(content.line_len(cur_pos.row)),
+                        content,
+                    );
+                    // the line itself is removed only if it is not the last one
+                    if cur_pos.row + 1 < content.line_count() {
+                        removed_text.push('\n');
+                    }
+                    Some(EditorCommand::CutLine {
+                        pos: cur_pos,
+                        removed_text,
+                    })
+                } else if *ch == 'd' && modifiers.ctrl {
+                    Some(EditorCommand::DuplicateLine {
+                        pos: cur_pos,
+                        inserted_text: Editor::clone_range(
+                            cur_pos.with_column(0),
+                            cur_pos.with_column(content.line_len(cur_pos.row)),
+                            content,
+                        ),
+                    })
+                } else if *ch == 'a' && modifiers.ctrl {
+                    None
+                } else if ch.to_ascii_lowercase() == 'z' && modifiers.ctrl && modifiers.shift {
+                    None
+                } else if ch.to_ascii_lowercase() == 'z' && modifiers.ctrl {
+                    None
+                } else if let Some((start, end)) = selection.is_range_ordered() {
+                    Some(EditorCommand::InsertCharSelection {
+                        ch: *ch,
+                        selection,
+                        selected_text: Editor::clone_range(start, end, content),
+                    })
+                } else if !self.insert_mode && !self.is_cursor_at_eol(content) {
+                    Some(EditorCommand::InsertCharSelection {
+                        ch: *ch,
+                        selection: Selection::range(cur_pos, cur_pos.with_next_col()),
+                        selected_text: content.get_char(cur_pos.row, cur_pos.column).to_string(),
+                    })
+                } else if content.line_len(cur_pos.row) == content.max_line_len() {
+                    None
+                } else {
+                    Some(EditorCommand::InsertChar {
+                        pos: cur_pos,
+                        ch: *ch,
+                    })
+                }
+            }
+        };
+    }
+
+    /// It is not undoable, e.g. for streamed output.
+    pub fn push_char<T: Default + Clone + Debug>(
+        &mut self,
+        ch: char,
+        content: &mut EditorContent<T>,
+    ) {
+        let pos = content.push_char(ch);
+        self.set_selection_save_col(Selection::single(pos));
+    }
+
+    pub fn insert_text_no_undo<T: Default + Clone + Debug>(
+        &mut self,
+        str: &str,
+        content: &mut EditorContent<T>,
+    ) -> Option<RowModificationType> {
+        self.insert_text(str, content, false)
+    }
+
+    pub fn insert_text_undoable<T: Default + Clone + Debug>(
+        &mut self,
+        str: &str,
+        content: &mut EditorContent<T>,
+    ) -> Option<RowModificationType> {
+        self.insert_text(str, content, true)
+    }
+
+    /// Applies an edit coming from an external source (e.g. another writer).
+    /// The selection and the mark keep covering the same text, or they are clamped to the start
+    /// of the edit if they were inside it. The edit clears the undo history, whose commands
+    /// would not fit the changed content.
+    pub fn apply_edit<T: Default + Clone + Debug>(
+        &mut self,
+        offset: usize,
+        removed_len: usize,
+        text: &str,
+        content: &mut EditorContent<T>,
+    ) -> Option<RowModificationType> {
+        let edit_start = content.offset_to_pos(offset);
+        let edit_end = content.offset_to_pos(offset + removed_len);
+        let replaced = ReplacedRange::new(edit_start, edit_end, content);
+        let command = if edit_start == edit_end {
+            if text.is_empty() {
+                return None;
+            }
+            EditorCommand::InsertText {
+                pos: edit_start,
+                text: text.to_owned(),
+                is_there_line_overflow: false,
+            }
+        } else {
+            let selection = Selection::range(edit_start, edit_end);
+            let removed_text = Editor::clone_range(edit_start, edit_end, content);
+            if text.is_empty() {
+                EditorCommand::DelSelection {
+                    removed_text,
+                    selection,
+                }
+            } else {
+                EditorCommand::InsertTextSelection {
+                    selection,
+                    text: text.to_owned(),
+                    removed_text,
+                    is_there_line_overflow: false,
+                }
+            }
+        };
+
+        let selection = self.selection;
+        let modif_type = self.execute_user_input(command, content, false);
+        if modif_type.is_some() {
+            // the commands of the undo stack would not fit the content any more
+            content.undo_stack.clear();
+            content.redo_stack.clear();
+        }
+        let (start, end) = selection.get_range();
+        // a collapsed cursor at the edit is moved after the inserted text,
+        // but the end of a selection starting there is not
+        let first = selection.get_first();
+        let new_start = replaced.map(start, start == first, content);
+        let new_end = replaced.map(end, end == first, content);
+        self.set_selection_save_col(Selection::range(new_start, new_end));
+        modif_type
+    }
+
+    pub fn insert_text_selected<T: Default + Clone + Debug>(
+        &mut self,
+        str: &str,
+        content: &mut EditorContent<T>,
+    ) -> Option<RowModificationType> {
+        let insert_pos = self.selection.get_first();
+        let modif_type = self.insert_text_undoable(str, content);
+        if modif_type.is_some() {
+            let end_pos = self.selection.get_cursor_pos();
+            self.set_selection_save_col(Selection::range(insert_pos, end_pos));
+        }
+        modif_type
+    }
+
+    pub fn surround_selection<T: Default + Clone + Debug>(
+        &mut self,
+        prefix: &str,
+        suffix: &str,
+        content: &mut EditorContent<T>,
+    ) -> Option<RowModificationType> {
+        let selection = self.selection;
+        let first = selection.get_first();
+        let second = selection.get_second();
+        let maxlen = content.max_line_len();
+        let text_start = Editor::get_str_range(prefix, first.row, first.column, maxlen);
+        // in single line mode the delimiters would be truncated, insert them fully or not at all
+        if self.single_line
+            && content.line_len(first.row) + prefix.chars().count() + suffix.chars().count()
+                > maxlen
+        {
+            return None;
+        }
+        if !selection.is_range() {
+            let modif_type = self.insert_text_undoable(&(prefix.to_owned() + suffix), content);
+            if modif_type.is_some() {
+                self.set_selection_save_col(Selection::single(text_start));
+            }
+            return modif_type;
+        }
+
+        self.set_selection_save_col(Selection::single(second));
+        let mut modif_type = self.insert_text_undoable(suffix, content);
+        if modif_type.is_none() {
+            self.set_selection_save_col(selection);
+            return None;
+        }
+        self.set_selection_save_col(Selection::single(first));
+        let prefix_modif_type = self.insert_text_undoable(prefix, content);
+        if prefix_modif_type.is_none() {
+            self.set_selection_save_col(selection);
+            return modif_type;
+        }
+        if let Some(modif_type) = &mut modif_type {
+            modif_type.merge(prefix_modif_type.as_ref());
+        }
+
+        let text_end = if second.row == first.row {
+            Pos::from_row_column(
+                text_start.row,
+                second.column - first.column + text_start.column,
+            )
+        } else {
+            second.with_row(second.row + text_start.row - first.row)
+        };
+        self.set_selection_save_col(if selection.start == first {
+            Selection::range(text_start, text_end)
+        } else {
+            Selection::range(text_end, text_start)
+        });
+        modif_type
+    }
+
+    /// Nothing happens if `f` returns a line break or a line longer than max_line_len.
+    pub fn map_selected_lines<T: Default + Clone + Debug, F: FnMut(&str) -> String>(
+        &mut self,
+        mut f: F,
+        content: &mut EditorContent<T>,
+    ) -> Option<RowModificationType> {
+        let first_row = self.selection.get_first().row;
+        let last_row = self.selection.get_second().row;
+        let mut text = String::with_capacity(content.max_line_len() * (last_row - first_row + 1));
+        let mut last_line_len = 0;
+        for row in first_row..=last_row {
+            let line: String = content.get_line_valid_chars(row).iter().collect();
+            let new_line = f(&line);
+            last_line_len = new_line.chars().count();
+            if new_line.contains('\n') || last_line_len > content.max_line_len() {
+                return None;
+            }
+            if row > first_row {
+                text.push('\n');
+            }
+            text.push_str(&new_line);
+        }
+        self.set_selection_save_col(Selection::range(
+            Pos::from_row_column(first_row, 0),
+            Pos::from_row_column(last_row, content.line_len(last_row)),
+        ));
+        let modif_type = self.insert_text_undoable(&text, content);
+        self.set_selection_save_col(Selection::range(
+            Pos::from_row_column(first_row, 0),
+            Pos::from_row_column(last_row, last_line_len),
+        ));
+        modif_type
+    }
+
+    pub fn join_lines_raw_no_undo<T: Default + Clone + Debug>(
+        &mut self,
+        content: &mut EditorContent<T>,
+    ) -> Option<RowModificationType> {
+        self.join_lines_raw(content, false)
+    }
+
+    pub fn join_lines_raw_undoable<T: Default + Clone + Debug>(
+        &mut self,
+        content: &mut EditorContent<T>,
+    ) -> Option<RowModificationType> {
+        self.join_lines_raw(content, true)
+    }
+
+    fn join_lines_raw<T: Default + Clone + Debug>(
+        &mut self,
+        content: &mut EditorContent<T>,
+        undoable: bool,
+    ) -> Option<RowModificationType> {
+        let cur_pos = self.selection.get_cursor_pos();
+        if cur_pos.row == 0 || !content.can_merge(cur_pos.row - 1) {
+            return None;
+        }
+        let upper_row_index = cur_pos.row - 1;
+        self.execute_user_input(
+            EditorCommand::MergeLineWithNextRow {
+                upper_row_index,
+                upper_line_data: Box::new(content.get_data(upper_row_index).clone()),
+                lower_line_data: Box::new(content.get_data(cur_pos.row).clone()),
+                pos_before_merge: cur_pos,
+                pos_after_merge: Pos::from_row_column(
+                    upper_row_index,
+                    content.line_len(upper_row_index),
+                ),
+            },
+            content,
+            undoable,
+        )
+    }
+
+    pub fn duplicate_selection_no_undo<T: Default + Clone + Debug>(
+        &mut self,
+        content: &mut EditorContent<T>,
+    ) -> Option<RowModificationType> {
+        self.duplicate_selection(content, false)
+    }
+
+    pub fn duplicate_selection_undoable<T: Default + Clone + Debug>(
+        &mut self,
+        content: &mut EditorContent<T>,
+    ) -> Option<RowModificationType> {
+        self.duplicate_selection(content, true)
+    }
+
+    /// Without selection, the char before the cursor is duplicated.
+    /// Only single line selections are supported.
+    fn duplicate_selection<T: Default + Clone + Debug>(
+        &mut self,
+        content: &mut EditorContent<T>,
+        undoable: bool,
+    ) -> Option<RowModificationType> {
+        let (start, end) = if self.selection.is_range() {
+            (self.selection.get_first(), self.selection.get_second())
+        } else {
+            let cur_pos = self.selection.get_cursor_pos();
+            if cur_pos.column == 0 {
+                return None;
+            }
+            (cur_pos.with_prev_col(), cur_pos)
+        };
+        let len = end.column - start.column;
+        if start.row != end.row || !content.can_insert(start.row, len) {
+            return None;
+        }
+        let was_range = self.selection.is_range();
+        let modif_type = self.execute_user_input(
+            EditorCommand::InsertText {
+                pos: end,
+                text: Editor::clone_range(start, end, content),
+                is_there_line_overflow: false,
+            },
+            content,
+            undoable,
+        );
+        if modif_type.is_some() && was_range {
+            self.set_selection_save_col(Selection::range(end, end.add_column(len)));
+        }
+        modif_type
+    }
+
+    pub fn prefix_all_lines<T: Default + Clone + Debug>(
+        &mut self,
+        prefix: &str,
+        skip_blank_lines: bool,
+        content: &mut EditorContent<T>,
+    ) -> Option<RowModificationType> {
+        self.map_all_lines(
+            |line| {
+                if skip_blank_lines && line.trim().is_empty() {
+                    line.to_owned()
                 } else {
-                    Some(EditorCommand::InsertChar {
-                        pos: cur_pos,
-                        ch: *ch,
-                    })
+                    prefix.to_owned() + line
                 }
-            }
-        };
+            },
+            content,
+        )
     }
 
-    pub fn insert_text_no_undo<T: Default + Clone + Debug>(
+    pub fn unprefix_all_lines<T: Default + Clone + Debug>(
+        &mut self,
+        prefix: &str,
+        content: &mut EditorContent<T>,
+    ) -> Option<RowModificationType> {
+        self.map_all_lines(
+            |line| line.strip_prefix(prefix).unwrap_or(line).to_owned(),
+            content,
+        )
+    }
+
+    /// Applies `f` to every line, which may only add or remove chars at the beginning of it,
+    /// the columns of the selection are shifted accordingly.
+    fn map_all_lines<T: Default + Clone + Debug, F: FnMut(&str) -> String>(
+        &mut self,
+        mut f: F,
+        content: &mut EditorContent<T>,
+    ) -> Option<RowModificationType> {
+        let selection = self.selection;
+        let mut len_deltas = Vec::with_capacity(content.line_count());
+        self.set_selection_save_col(Selection::range(
+            Pos::from_row_column(0, 0),
+            Pos::from_row_column(content.line_count() - 1, 0),
+        ));
+        let modif_type = self.map_selected_lines(
+            |line| {
+                let new_line = f(line);
+                len_deltas.push(new_line.chars().count() as isize - line.chars().count() as isize);
+                new_line
+            },
+            content,
+        );
+        if modif_type.is_none() {
+            self.set_selection_save_col(selection);
+            return None;
+        }
+        let shift =
+            |pos: Pos| pos.with_column((pos.column as isize + len_deltas[pos.row]).max(0) as usize);
+        self.set_selection_save_col(Selection::range(
+            shift(selection.start),
+            shift(selection.end.unwrap_or(selection.start)),
+        ));
+        modif_type
+    }
+
+    pub fn paste<T: Default + Clone + Debug>(
         &mut self,
         str: &str,
+        policy: OverflowPolicy,
         content: &mut EditorContent<T>,
     ) -> Option<RowModificationType> {
-        self.insert_text(str, content, false)
+        let str = self.limit_paste(str);
+        if policy == OverflowPolicy::Wrap {
+            return self.insert_text_undoable(str, content);
+        }
+        let first = self.selection.get_first();
+        let second = self.selection.get_second();
+        let tail_len = content.line_len(second.row) - second.column;
+        let line_count = str.split('\n').count();
+        let mut text = String::with_capacity(str.len());
+        for (i, line) in str.split('\n').enumerate() {
+            let mut limit = content.max_line_len();
+            if i == 0 {
+                limit -= first.column;
+            }
+            if i == line_count - 1 {
+                limit = limit.saturating_sub(tail_len);
+            }
+            let line_len = line.chars().filter(|it| *it != '\r').count();
+            if line_len > limit && policy == OverflowPolicy::Reject {
+                return None;
+            }
+            if i > 0 {
+                text.push('\n');
+            }
+            text.extend(line.chars().filter(|it| *it != '\r').take(limit));
+        }
+        self.insert_text_undoable(&text, content)
     }
 
-    pub fn insert_text_undoable<T: Default + Clone + Debug>(
+    fn limit_paste<'a>(&mut self, str: &'a str) -> &'a str {
+        self.dropped_paste_chars = 0;
+        match self
+            .max_paste_chars
+            .and_then(|max| str.char_indices().nth(max))
+        {
+            Some((truncate_at, _)) => {
+                self.dropped_paste_chars = str[truncate_at..].chars().count();
+                &str[0..truncate_at]
+            }
+            None => str,
+        }
+    }
+
+    /// Pastes the text after removing the common leading whitespace of its lines and
+    /// indenting all but the first of them like the current line (the first one continues
+    /// the line at the cursor). Blank lines stay empty.
+    pub fn paste_matching_indent<T: Default + Clone + Debug>(
         &mut self,
         str: &str,
         content: &mut EditorContent<T>,
     ) -> Option<RowModificationType> {
-        self.insert_text(str, content, true)
+        let str = self.limit_paste(str);
+        let leading_ws_len = |line: &str| {
+            line.chars()
+                .take_while(|ch| *ch == ' ' || *ch == '\t')
+                .count()
+        };
+        let lines: Vec<&str> = str
+            .split('\n')
+            .map(|line| line.trim_end_matches('\r'))
+            .collect();
+        let common_indent = lines
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| leading_ws_len(line))
+            .min()
+            .unwrap_or(0);
+        let target_indent: String = content
+            .get_line_valid_chars(self.selection.get_first().row)
+            .iter()
+            .take_while(|ch| **ch == ' ' || **ch == '\t')
+            .collect();
+        let mut text = String::with_capacity(str.len() + lines.len() * target_indent.len());
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                text.push('\n');
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            if i > 0 {
+                text.push_str(&target_indent);
+            }
+            text.extend(line.chars().skip(common_indent));
+        }
+        self.insert_text_undoable(&text, content)
     }
 
     fn insert_text<T: Default + Clone + Debug>(
@@ -665,11 +2431,44 @@ impl Editor {
         content: &mut EditorContent<T>,
         undoable: bool,
     ) -> Option<RowModificationType> {
-        let selection = self.selection;
+        self.leave_virtual_space(true, content, undoable);
+        let single_line_text;
+        let str = if self.single_line && str.contains('\n') {
+            single_line_text = str.replace("\r\n", " ").replace('\n', " ");
+            &single_line_text
+        } else {
+            str
+        };
+        let selection = if !self.insert_mode && !self.selection.is_range() && !str.contains('\n') {
+            // overtype: the pasted text replaces the same amount of chars after the cursor
+            let cur_pos = self.selection.get_cursor_pos();
+            let overwritten_len = str
+                .chars()
+                .count()
+                .min(content.line_len(cur_pos.row) - cur_pos.column);
+            Selection::range(cur_pos, cur_pos.add_column(overwritten_len))
+        } else {
+            self.selection
+        };
+        let str = if self.single_line {
+            // the text which does not fit into the line is dropped instead of being wrapped
+            let first = selection.get_first();
+            let kept_len =
+                content.line_len(first.row) - (selection.get_second().column - first.column);
+            match str.char_indices().nth(content.max_line_len() - kept_len) {
+                Some((0, _)) => return None,
+                Some((truncate_at, _)) => &str[0..truncate_at],
+                None => str,
+            }
+        } else {
+            str
+        };
         let cur_pos = selection.get_first();
         let inserted_text_end_pos =
             Editor::get_str_range(str, cur_pos.row, cur_pos.column, content.max_line_len());
-        let remaining_text_len_in_this_row = content.line_len(cur_pos.row) - cur_pos.column;
+        let after_selection = selection.get_second();
+        let remaining_text_len_in_this_row =
+            content.line_len(after_selection.row) - after_selection.column;
         let is_there_line_overflow =
             inserted_text_end_pos.column + remaining_text_len_in_this_row > content.max_line_len();
         let command = if let Some((start, end)) = selection.is_range_ordered() {
@@ -690,6 +2489,16 @@ impl Editor {
         return self.execute_user_input(command, content, undoable);
     }
 
+    pub fn handle_input_str<T: Default + Clone + Debug>(
+        &mut self,
+        spec: &str,
+        content: &mut EditorContent<T>,
+    ) -> Result<Option<RowModificationType>, String> {
+        let (input, modifiers) = EditorInputEvent::parse_spec(spec)
+            .ok_or_else(|| format!("Unrecognized keystroke: '{}'", spec))?;
+        Ok(self.handle_input_undoable(input, modifiers, content))
+    }
+
     pub fn handle_input_no_undo<T: Default + Clone + Debug>(
         &mut self,
         input: EditorInputEvent,
@@ -715,13 +2524,61 @@ impl Editor {
         content: &mut EditorContent<T>,
         undoable: bool,
     ) -> Option<RowModificationType> {
+        self.reset_blink();
+        if self.single_line && input == EditorInputEvent::Enter {
+            self.last_input_effect = InputEffect::Submit;
+            return None;
+        }
+        if self.would_exceed_max_line_count(input, modifiers, content) {
+            self.last_input_effect = InputEffect::Rejected;
+            return None;
+        }
+        let cursor_pos = self.selection.get_cursor_pos();
+        let line_len = content.line_len(cursor_pos.row);
+        match input {
+            EditorInputEvent::Backspace | EditorInputEvent::Del
+                if !self.selection.is_range() && cursor_pos.column > line_len =>
+            {
+                // nothing is deleted, the cursor just snaps back to the end of the line
+                self.set_selection_save_col(Selection::single(cursor_pos.with_column(line_len)));
+                return None;
+            }
+            EditorInputEvent::Char(_)
+            | EditorInputEvent::Enter
+            | EditorInputEvent::Tab
+            | EditorInputEvent::Backspace
+            | EditorInputEvent::Del => {
+                let inserts_text = match input {
+                    EditorInputEvent::Char(_) => !modifiers.ctrl && !modifiers.alt,
+                    EditorInputEvent::Enter | EditorInputEvent::Tab => true,
+                    _ => false,
+                };
+                self.leave_virtual_space(inserts_text, content, undoable);
+            }
+            _ => {}
+        }
         if (input == EditorInputEvent::Char('x') || input == EditorInputEvent::Char('c'))
             && modifiers.ctrl
         {
             self.send_selection_to_clipboard(self.selection, content);
         }
 
-        match input {
+        let selection_before = self.selection;
+        // the row without its trailing whitespace, to detect edits which changed only that
+        let trimmed_row_before: Option<(usize, Vec<char>)> = match input {
+            EditorInputEvent::Char(_)
+            | EditorInputEvent::Tab
+            | EditorInputEvent::Backspace
+            | EditorInputEvent::Del => {
+                let row = self.selection.get_cursor_pos().row;
+                Some((
+                    row,
+                    Editor::trim_end_chars(content.get_line_valid_chars(row)).to_vec(),
+                ))
+            }
+            _ => None,
+        };
+        let modif_type = match input {
             EditorInputEvent::Char(ch)
                 if ch.to_ascii_lowercase() == 'z' && modifiers.is_ctrl_shift() =>
             {
@@ -730,17 +2587,253 @@ impl Editor {
             EditorInputEvent::Char(ch) if ch.to_ascii_lowercase() == 'z' && modifiers.ctrl => {
                 self.undo(content)
             }
+            EditorInputEvent::Tab if modifiers.shift && self.selection.is_range() => {
+                self.dedent_selection(content, undoable)
+            }
+            EditorInputEvent::Up | EditorInputEvent::Down if modifiers.alt => {
+                // moves the current line or every line touched by the selection,
+                // a selection ending at the start of a row does not touch that row
+                let first = self.selection.get_first();
+                let second = self.selection.get_second();
+                let last_row = if second.column == 0 && second.row > first.row {
+                    second.row - 1
+                } else {
+                    second.row
+                };
+                let direction = if input == EditorInputEvent::Up { -1 } else { 1 };
+                let last_column_index = self.last_column_index;
+                let modif_type =
+                    self.move_block(first.row..last_row + 1, direction, content, undoable);
+                self.last_column_index = last_column_index;
+                modif_type
+            }
+            EditorInputEvent::Char('x') if modifiers.ctrl && self.selection.is_range() => {
+                match self.cut(content, undoable) {
+                    Some((_text, modif_type)) => modif_type,
+                    None => None,
+                }
+            }
             _ => {
+                if input == EditorInputEvent::Backspace && self.strip_indent_on_join {
+                    self.strip_indent_before_join(content, undoable);
+                }
+                let merges_with_ctrl_del = input == EditorInputEvent::Del
+                    && modifiers.ctrl
+                    && self.ctrl_del_crosses_line
+                    && !self.selection.is_range()
+                    && self.is_cursor_at_eol(content);
                 if let Some(command) = self.create_command(&input, modifiers, content) {
-                    self.execute_user_input(command, content, undoable)
+                    let mut modif_type = self.execute_user_input(command, content, undoable);
+                    if merges_with_ctrl_del && modif_type.is_some() {
+                        let word_modif_type = self.del_first_word_after_merge(content, undoable);
+                        if let Some(modif_type) = &mut modif_type {
+                            modif_type.merge(word_modif_type.as_ref());
+                        }
+                    }
+                    modif_type
                 } else {
-                    self.next_blink_at = self.time + EDITOR_CURSOR_TICK_MS;
-                    self.show_cursor = true;
                     self.handle_navigation_input(&input, modifiers, content);
                     None
                 }
             }
+        };
+        self.last_input_effect = match modif_type {
+            Some(RowModificationType::SingleLine(row))
+                if trimmed_row_before
+                    .as_ref()
+                    .map_or(false, |(before_row, before)| {
+                        *before_row == row
+                            && before[..]
+                                == *Editor::trim_end_chars(content.get_line_valid_chars(row))
+                    }) =>
+            {
+                InputEffect::WhitespaceOnly(row)
+            }
+            Some(RowModificationType::SingleLine(row))
+            | Some(RowModificationType::AllLinesFrom(row)) => InputEffect::TextChanged(row),
+            None if self.selection != selection_before => InputEffect::SelectionChanged,
+            None => InputEffect::NoChange,
+        };
+        modif_type
+    }
+
+    fn trim_end_chars(line: &[char]) -> &[char] {
+        let trailing_ws_len = line
+            .iter()
+            .rev()
+            .take_while(|ch| ch.is_whitespace())
+            .count();
+        &line[0..line.len() - trailing_ws_len]
+    }
+
+    fn outdent_len(&self, line: &[char]) -> usize {
+        if line.first() == Some(&'\t') {
+            1
+        } else {
+            self.leading_spaces_to_dedent(line)
+        }
+    }
+
+    fn leading_spaces_to_dedent(&self, line: &[char]) -> usize {
+        line.iter()
+            .take(self.tab_width)
+            .take_while(|ch| **ch == ' ')
+            .count()
+    }
+
+    /// Removes at most tab_width leading spaces from every row touched by the selection,
+    /// keeping the selected text selected. Rows without leading spaces are left untouched,
+    /// as well as the last row if the selection ends at its beginning.
+    fn dedent_selection<T: Default + Clone + Debug>(
+        &mut self,
+        content: &mut EditorContent<T>,
+        undoable: bool,
+    ) -> Option<RowModificationType> {
+        let (start, end) = self.selection.get_range();
+        let first = self.selection.get_first();
+        let second = self.selection.get_second();
+        let last_row = if second.column == 0 && second.row > first.row {
+            second.row - 1
+        } else {
+            second.row
+        };
+        let mut modif_type: Option<RowModificationType> = None;
+        let mut start_col = start.column;
+        let mut end_col = end.column;
+        for row in first.row..=last_row {
+            let line = content.get_line_valid_chars(row);
+            let removed_len = self.leading_spaces_to_dedent(line);
+            if removed_len == 0 {
+                continue;
+            }
+            let command = EditorCommand::OutdentLine {
+                pos: Pos::from_row_column(row, 0),
+                removed_text: line[0..removed_len].iter().collect(),
+            };
+            let row_modif_type = self.execute_user_input(command, content, undoable);
+            if row == start.row {
+                start_col = start_col.saturating_sub(removed_len);
+            }
+            if row == end.row {
+                end_col = end_col.saturating_sub(removed_len);
+            }
+            match &mut modif_type {
+                Some(modif_type) => modif_type.merge(row_modif_type.as_ref()),
+                None => modif_type = row_modif_type,
+            }
+        }
+        self.set_selection_save_col(Selection::range(
+            start.with_column(start_col),
+            end.with_column(end_col),
+        ));
+        modif_type
+    }
+
+    /// Editing operations work on real text only: if the cursor is in virtual space,
+    /// either the gap is filled with spaces (when text is about to be inserted),
+    /// or the selection is clamped to the end of the lines.
+    fn leave_virtual_space<T: Default + Clone + Debug>(
+        &mut self,
+        fill_with_spaces: bool,
+        content: &mut EditorContent<T>,
+        undoable: bool,
+    ) {
+        let start = self.selection.start;
+        let end = self.selection.end;
+        let is_virtual = |pos: Pos| pos.column > content.line_len(pos.row);
+        if !is_virtual(start) && !end.map(is_virtual).unwrap_or(false) {
+            return;
+        }
+        if fill_with_spaces && !self.selection.is_range() {
+            let line_len = content.line_len(start.row);
+            let spaces = std::iter::repeat(' ')
+                .take(start.column - line_len)
+                .collect::<String>();
+            self.execute_user_input(
+                EditorCommand::InsertText {
+                    pos: start.with_column(line_len),
+                    text: spaces,
+                    is_there_line_overflow: false,
+                },
+                content,
+                undoable,
+            );
+        } else {
+            let clamp = |pos: Pos| pos.with_column(pos.column.min(content.line_len(pos.row)));
+            self.set_selection_save_col(Selection::range(
+                clamp(start),
+                clamp(end.unwrap_or(start)),
+            ));
+        }
+    }
+
+    fn del_first_word_after_merge<T: Default + Clone + Debug>(
+        &mut self,
+        content: &mut EditorContent<T>,
+        undoable: bool,
+    ) -> Option<RowModificationType> {
+        let mut modif_type: Option<RowModificationType> = None;
+        for _ in 0..2 {
+            let cur_pos = self.selection.get_cursor_pos();
+            if self.is_cursor_at_eol(content) {
+                break;
+            }
+            let is_whitespace = content
+                .get_char(cur_pos.row, cur_pos.column)
+                .is_ascii_whitespace();
+            let col = content.jump_word_forward(&cur_pos, JumpMode::ConsiderWhitespaces);
+            let command = EditorCommand::DelCtrl {
+                removed_text: Some(Editor::clone_range(
+                    cur_pos,
+                    cur_pos.with_column(col),
+                    content,
+                )),
+                pos: cur_pos,
+            };
+            let del_modif_type = self.execute_user_input(command, content, undoable);
+            modif_type = match modif_type {
+                Some(mut modif_type) => {
+                    modif_type.merge(del_modif_type.as_ref());
+                    Some(modif_type)
+                }
+                None => del_modif_type,
+            };
+            if !is_whitespace {
+                break;
+            }
+        }
+        modif_type
+    }
+
+    fn strip_indent_before_join<T: Default + Clone + Debug>(
+        &mut self,
+        content: &mut EditorContent<T>,
+        undoable: bool,
+    ) {
+        let cur_pos = self.selection.get_cursor_pos();
+        if self.selection.is_range() || cur_pos.column != 0 || cur_pos.row == 0 {
+            return;
         }
+        let indent_len = content
+            .get_line_valid_chars(cur_pos.row)
+            .iter()
+            .take_while(|ch| ch.is_ascii_whitespace())
+            .count();
+        let merged_len =
+            content.line_len(cur_pos.row - 1) + content.line_len(cur_pos.row) - indent_len;
+        if indent_len == 0 || merged_len > content.max_line_len() {
+            return;
+        }
+        // Ctrl-Del at the beginning of the line removes exactly the leading whitespaces
+        let command = EditorCommand::DelCtrl {
+            removed_text: Some(Editor::clone_range(
+                cur_pos,
+                cur_pos.with_column(indent_len),
+                content,
+            )),
+            pos: cur_pos,
+        };
+        self.execute_user_input(command, content, undoable);
     }
 
     fn execute_user_input<T: Default + Clone + Debug>(
@@ -749,9 +2842,19 @@ impl Editor {
         content: &mut EditorContent<T>,
         undoable: bool,
     ) -> Option<RowModificationType> {
-        self.next_blink_at = self.time + EDITOR_CURSOR_TICK_MS;
-        self.show_cursor = true;
+        self.reset_blink();
+        let replaced = match self.mark {
+            Some(_) => Editor::replaced_range_of(&command, content),
+            None => None,
+        };
         let modif_type = self.do_command(&command, content);
+        if modif_type.is_some() {
+            // the clicked position might not exist anymore
+            self.drag_anchor = None;
+            self.mark = self
+                .mark
+                .map(|mark| Editor::map_pos_through(mark, &command, replaced.as_ref(), content));
+        }
         if modif_type.is_some() && undoable {
             if self.modif_time_treshold_expires_at < self.time || content.undo_stack.is_empty() {
                 // new undo group
@@ -764,6 +2867,104 @@ impl Editor {
         modif_type
     }
 
+    fn replaced_range_of<T: Default + Clone + Debug>(
+        command: &EditorCommand<T>,
+        content: &EditorContent<T>,
+    ) -> Option<ReplacedRange> {
+        let row_start = |row: usize| Pos::from_row_column(row, 0);
+        let row_end = |row: usize| Pos::from_row_column(row, content.line_len(row));
+        let (start, end) = match command {
+            EditorCommand::SwapLineUpwards(_)
+            | EditorCommand::SwapLineDownards(_)
+            | EditorCommand::SwapRows { .. }
+            | EditorCommand::ReplaceRows(_) => return None,
+            EditorCommand::InsertText { pos, .. }
+            | EditorCommand::InsertChar { pos, .. }
+            | EditorCommand::Enter(pos) => (*pos, *pos),
+            EditorCommand::InsertTextSelection { selection, .. }
+            | EditorCommand::InsertCharSelection { selection, .. }
+            | EditorCommand::EnterSelection { selection, .. }
+            | EditorCommand::DelSelection { selection, .. }
+            | EditorCommand::BackspaceSelection { selection, .. } => {
+                (selection.get_first(), selection.get_second())
+            }
+            EditorCommand::Del { pos, .. } => {
+                if content.line_len(pos.row) == 0 && content.line_count() > 1 {
+                    (row_start(pos.row), row_start(pos.row + 1))
+                } else if pos.column < content.line_len(pos.row) {
+                    (*pos, pos.with_next_col())
+                } else if pos.row + 1 < content.line_count() {
+                    (*pos, row_start(pos.row + 1))
+                } else {
+                    (*pos, *pos)
+                }
+            }
+            EditorCommand::DelCtrl { pos, .. } => {
+                let col = content.jump_word_forward(pos, JumpMode::ConsiderWhitespaces);
+                (*pos, pos.with_column(col))
+            }
+            EditorCommand::Backspace { pos, .. } => (pos.with_column(pos.column - 1), *pos),
+            EditorCommand::BackspaceCtrl { pos, .. } => {
+                let col = content.jump_word_backward(pos, JumpMode::IgnoreWhitespaces);
+                (pos.with_column(col), *pos)
+            }
+            EditorCommand::MergeLineWithNextRow {
+                upper_row_index, ..
+            } => (row_end(*upper_row_index), row_start(*upper_row_index + 1)),
+            EditorCommand::InsertEmptyRow(row) => (row_start(*row), row_start(*row)),
+            EditorCommand::CutLine { pos, .. } => {
+                if pos.row + 1 < content.line_count() {
+                    (row_start(pos.row), row_start(pos.row + 1))
+                } else {
+                    (row_start(pos.row), row_end(pos.row))
+                }
+            }
+            EditorCommand::DuplicateLine { pos, .. } => (row_end(pos.row), row_end(pos.row)),
+            EditorCommand::OutdentLine { pos, removed_text } => (
+                row_start(pos.row),
+                pos.with_column(removed_text.chars().count()),
+            ),
+        };
+        Some(ReplacedRange::new(start, end, content))
+    }
+
+    fn map_pos_through<T: Default + Clone + Debug>(
+        pos: Pos,
+        command: &EditorCommand<T>,
+        replaced: Option<&ReplacedRange>,
+        content: &EditorContent<T>,
+    ) -> Pos {
+        let (a, b) = match command {
+            EditorCommand::SwapLineUpwards(pos) => (pos.row - 1, pos.row),
+            EditorCommand::SwapLineDownards(pos) => (pos.row, pos.row + 1),
+            EditorCommand::SwapRows { a, b } => (*a, *b),
+            EditorCommand::ReplaceRows(snapshot) => {
+                let first_row = snapshot.first_row;
+                let replaced_to = first_row + snapshot.rows_before.len();
+                return if pos.row < first_row {
+                    pos
+                } else if pos.row < replaced_to {
+                    Pos::from_row_column(first_row, 0)
+                } else {
+                    pos.with_row(pos.row + snapshot.rows_after.len() - snapshot.rows_before.len())
+                };
+            }
+            _ => {
+                return match replaced {
+                    Some(replaced) => replaced.map(pos, true, content),
+                    None => pos,
+                }
+            }
+        };
+        if pos.row == a {
+            pos.with_row(b)
+        } else if pos.row == b {
+            pos.with_row(a)
+        } else {
+            pos
+        }
+    }
+
     fn do_command<T: Default + Clone + Debug>(
         &mut self,
         command: &EditorCommand<T>,
@@ -796,12 +2997,18 @@ impl Editor {
             }
             EditorCommand::SwapLineUpwards(pos) => {
                 content.swap_lines_upward(pos.row);
-                self.selection = Selection::single(Pos::from_row_column(pos.row - 1, pos.column));
+                self.set_selection_save_col(Selection::single(Pos::from_row_column(
+                    pos.row - 1,
+                    pos.column,
+                )));
                 Some(RowModificationType::AllLinesFrom(pos.row - 1))
             }
             EditorCommand::SwapLineDownards(pos) => {
                 content.swap_lines_upward(pos.row + 1);
-                self.selection = Selection::single(Pos::from_row_column(pos.row + 1, pos.column));
+                self.set_selection_save_col(Selection::single(Pos::from_row_column(
+                    pos.row + 1,
+                    pos.column,
+                )));
                 Some(RowModificationType::AllLinesFrom(pos.row))
             }
             EditorCommand::Del {
@@ -826,7 +3033,7 @@ impl Editor {
                     content.remove_char(pos.row, pos.column);
                     Some(RowModificationType::SingleLine(pos.row))
                 };
-                self.selection = Selection::single(*pos);
+                self.set_selection_save_col(Selection::single(*pos));
                 modif_type
             }
             EditorCommand::DelSelection {
@@ -848,13 +3055,13 @@ impl Editor {
                 let new_pos = pos.with_column(col);
                 // TODO csinálj egy optimaliált metódust ami biztos h az adott sorból töröl csak
                 content.remove_selection(Selection::range(*pos, new_pos));
-                self.selection = Selection::single(*pos);
+                self.set_selection_save_col(Selection::single(*pos));
                 Some(RowModificationType::SingleLine(new_pos.row))
             }
-            EditorCommand::InsertEmptyRow(_) => {
-                // TODO
-                // Meg a Ctrl-D-t is
-                None
+            EditorCommand::InsertEmptyRow(row) => {
+                content.insert_line_at(*row);
+                self.shift_rows_in_selection(*row, 1);
+                Some(RowModificationType::AllLinesFrom(*row))
             }
             EditorCommand::EnterSelection {
                 selection,
@@ -980,6 +3187,35 @@ impl Editor {
                 self.set_selection_save_col(Selection::single(pos.with_next_row()));
                 Some(RowModificationType::AllLinesFrom(pos.row))
             }
+            EditorCommand::OutdentLine { pos, removed_text } => {
+                let removed_len = removed_text.chars().count();
+                content.remove_selection(Selection::range(
+                    pos.with_column(0),
+                    pos.with_column(removed_len),
+                ));
+                self.set_selection_save_col(Selection::single(
+                    pos.with_column(pos.column.saturating_sub(removed_len)),
+                ));
+                Some(RowModificationType::SingleLine(pos.row))
+            }
+            EditorCommand::SwapRows { a, b } => {
+                content.swap_lines(*a, *b);
+                self.swap_rows_in_selection(*a, *b);
+                Some(RowModificationType::AllLinesFrom((*a).min(*b)))
+            }
+            EditorCommand::ReplaceRows(snapshot) => {
+                content.replace_rows(
+                    snapshot.first_row,
+                    snapshot.rows_before.len(),
+                    &snapshot.rows_after,
+                );
+                content.set_detected_line_ending(snapshot.line_ending_after);
+                self.set_selection_save_col(snapshot.selection_after);
+                // only the line ending might have changed
+                Some(RowModificationType::AllLinesFrom(
+                    snapshot.first_row.min(content.line_count() - 1),
+                ))
+            }
         }
     }
 
@@ -1013,28 +3249,47 @@ impl Editor {
 
         match input {
             EditorInputEvent::PageUp => {
-                let new_pos = Pos::from_row_column(0, 0);
-                let new_selection = if modifiers.shift {
+                let new_pos = if cur_pos.row < self.page_size {
+                    Pos::from_row_column(0, 0)
+                } else {
+                    let row = cur_pos.row - self.page_size;
+                    Pos::from_row_column(row, self.last_column_index.min(content.line_len(row)))
+                };
+                self.set_selection_save_col(if modifiers.shift {
                     self.selection.extend(new_pos)
                 } else {
                     Selection::single(new_pos)
-                };
-                self.set_selection_save_col(new_selection);
+                });
             }
             EditorInputEvent::PageDown => {
-                let new_pos = Pos::from_row_column(
-                    content.line_count() - 1,
-                    content.line_len(content.line_count() - 1),
-                );
-                let new_selection = if modifiers.shift {
+                let last_row = content.line_count() - 1;
+                let new_pos = if cur_pos.row.saturating_add(self.page_size) > last_row {
+                    Pos::from_row_column(last_row, content.line_len(last_row))
+                } else {
+                    let row = cur_pos.row + self.page_size;
+                    Pos::from_row_column(row, self.last_column_index.min(content.line_len(row)))
+                };
+                self.set_selection_save_col(if modifiers.shift {
                     self.selection.extend(new_pos)
                 } else {
                     Selection::single(new_pos)
-                };
-                self.set_selection_save_col(new_selection);
+                });
             }
+            // Home/End without shift collapse the selection to the line start/end
+            // of its active end (where the cursor is), not of its first/last position
             EditorInputEvent::Home => {
-                let new_pos = cur_pos.with_column(0);
+                // Home jumps to the first non-whitespace char of the line,
+                // or to the line start if the cursor is already there
+                let first_non_ws = content
+                    .get_line_valid_chars(cur_pos.row)
+                    .iter()
+                    .position(|ch| !ch.is_ascii_whitespace())
+                    .unwrap_or(0);
+                let new_pos = if cur_pos.column == first_non_ws {
+                    cur_pos.with_column(0)
+                } else {
+                    cur_pos.with_column(first_non_ws)
+                };
                 let new_selection = if modifiers.shift {
                     self.selection.extend(new_pos)
                 } else {
@@ -1043,7 +3298,7 @@ impl Editor {
                 self.set_selection_save_col(new_selection);
             }
             EditorInputEvent::End => {
-                let new_pos = cur_pos.with_column(content.line_len(cur_pos.row));
+                let new_pos = cur_pos.with_column(self.max_column(cur_pos.row, content));
                 let new_selection = if modifiers.shift {
                     self.selection.extend(new_pos)
                 } else {
@@ -1162,14 +3417,14 @@ impl Editor {
                         cur_pos.with_column(prev_index),
                         cur_pos.with_column(next_index),
                     ));
-                } else if *ch == 'a' && modifiers.ctrl {
+                } else if *ch == 'l' && modifiers.ctrl {
+                    // select the whole line (without the line break) so typing replaces it
                     self.set_selection_save_col(Selection::range(
-                        Pos::from_row_column(0, 0),
-                        Pos::from_row_column(
-                            content.line_count() - 1,
-                            content.line_len(content.line_count() - 1),
-                        ),
+                        cur_pos.with_column(0),
+                        cur_pos.with_column(content.line_len(cur_pos.row)),
                     ));
+                } else if *ch == 'a' && modifiers.ctrl {
+                    self.select_all(content);
                 }
             }
             EditorInputEvent::Del
@@ -1195,6 +3450,7 @@ impl Editor {
                 }
             }
             content.redo_stack.push(command_group);
+            self.drag_anchor = None;
         };
         sum_modif_type
     }
@@ -1214,6 +3470,7 @@ impl Editor {
                 }
             }
             content.undo_stack.push(command_group);
+            self.drag_anchor = None;
         };
         sum_modif_type
     }
@@ -1226,12 +3483,12 @@ impl Editor {
         match command {
             EditorCommand::SwapLineUpwards(pos) => {
                 content.swap_lines_upward(pos.row);
-                self.selection = Selection::single(*pos);
+                self.set_selection_save_col(Selection::single(*pos));
                 Some(RowModificationType::AllLinesFrom(pos.row - 1))
             }
             EditorCommand::SwapLineDownards(pos) => {
                 content.swap_lines_upward(pos.row + 1);
-                self.selection = Selection::single(*pos);
+                self.set_selection_save_col(Selection::single(*pos));
                 Some(RowModificationType::AllLinesFrom(pos.row))
             }
             EditorCommand::Del { removed_char, pos } => {
@@ -1280,9 +3537,10 @@ impl Editor {
                 self.set_selection_save_col(Selection::single(*pos_before_merge));
                 Some(RowModificationType::AllLinesFrom(*upper_row_index))
             }
-            EditorCommand::InsertEmptyRow(_) => {
-                // TODO
-                None
+            EditorCommand::InsertEmptyRow(row) => {
+                content.remove_line_at(*row);
+                self.shift_rows_in_selection(*row + 1, -1);
+                Some(RowModificationType::AllLinesFrom(*row))
             }
             EditorCommand::EnterSelection {
                 selection,
@@ -1354,9 +3612,6 @@ impl Editor {
                 }
             }
             EditorCommand::CutLine { pos, removed_text } => {
-                if pos.row != content.line_count() - 1 {
-                    content.insert_line_at(pos.row);
-                }
                 content.insert_str_at(pos.with_column(0), removed_text);
                 self.set_selection_save_col(Selection::single(*pos));
                 Some(RowModificationType::AllLinesFrom(pos.row))
@@ -1366,6 +3621,16 @@ impl Editor {
                 self.set_selection_save_col(Selection::single(*pos));
                 Some(RowModificationType::AllLinesFrom(pos.row + 1))
             }
+            EditorCommand::OutdentLine { pos, removed_text } => {
+                content.insert_str_at(pos.with_column(0), removed_text);
+                self.set_selection_save_col(Selection::single(*pos));
+                Some(RowModificationType::SingleLine(pos.row))
+            }
+            EditorCommand::SwapRows { a, b } => {
+                content.swap_lines(*a, *b);
+                self.swap_rows_in_selection(*a, *b);
+                Some(RowModificationType::AllLinesFrom((*a).min(*b)))
+            }
             EditorCommand::InsertText {
                 pos,
                 text,
@@ -1419,6 +3684,18 @@ impl Editor {
                     Some(RowModificationType::AllLinesFrom(first.row))
                 }
             }
+            EditorCommand::ReplaceRows(snapshot) => {
+                content.replace_rows(
+                    snapshot.first_row,
+                    snapshot.rows_after.len(),
+                    &snapshot.rows_before,
+                );
+                content.set_detected_line_ending(snapshot.line_ending_before);
+                self.set_selection_save_col(snapshot.selection_before);
+                Some(RowModificationType::AllLinesFrom(
+                    snapshot.first_row.min(content.line_count() - 1),
+                ))
+            }
         }
     }
 
@@ -1,5 +1,7 @@
 use crate::editor::editor::{Pos, RowModificationType, Selection};
 use smallvec::alloc::fmt::Debug;
+use std::collections::HashMap;
+use std::ops::Range;
 
 pub type Canvas = Vec<char>;
 type EditorCommandGroup<T> = Vec<EditorCommand<T>>;
@@ -62,6 +64,14 @@ pub enum EditorCommand<T: Default + Clone + Debug> {
         pos: Pos,
         inserted_text: String,
     },
+    OutdentLine {
+        pos: Pos,
+        removed_text: String,
+    },
+    SwapRows {
+        a: usize,
+        b: usize,
+    },
     InsertText {
         pos: Pos,
         text: String,
@@ -73,6 +83,78 @@ pub enum EditorCommand<T: Default + Clone + Debug> {
         removed_text: String,
         is_there_line_overflow: bool,
     },
+    ReplaceRows(Box<RowsSnapshot<T>>),
+}
+
+/// The rows which differ between two versions of the content (the common rows at the
+/// beginning and at the end are left out), the selections and the line endings, so the change
+/// can be applied in both directions without storing the whole content.
+/// The other commands store only the text they inserted or removed, which is even less,
+/// so only the replacement of the whole content (Editor::set_content_preserving_cursor)
+/// is stored this way.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct RowsSnapshot<T> {
+    pub first_row: usize,
+    pub rows_before: Vec<RowSnapshot<T>>,
+    pub rows_after: Vec<RowSnapshot<T>>,
+    pub selection_before: Selection,
+    pub selection_after: Selection,
+    pub line_ending_before: LineEnding,
+    pub line_ending_after: LineEnding,
+}
+
+/// A row with everything which belongs to it, so a replaced row can be restored completely
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct RowSnapshot<T> {
+    pub chars: Vec<char>,
+    pub data: T,
+    pub soft_breaks: Vec<usize>,
+    pub bookmarked: bool,
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum RowDiff {
+    /// the row of the other text does not exist in the content
+    Added(usize),
+    /// the row of the content does not exist in the other text
+    Removed(usize),
+    /// the row of the content was replaced by `other_row` of the other text
+    Changed { row: usize, other_row: usize },
+}
+
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Edit {
+    pub offset: usize,
+    pub removed_len: usize,
+    pub text: String,
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum CharClass {
+    Word,
+    Whitespace,
+    Quote,
+    Symbol,
+}
+
+impl CharClass {
+    pub fn of(ch: char) -> CharClass {
+        if ch.is_alphanumeric() || ch == '_' {
+            CharClass::Word
+        } else if ch == '\"' {
+            CharClass::Quote
+        } else if ch.is_ascii_whitespace() {
+            CharClass::Whitespace
+        } else {
+            CharClass::Symbol
+        }
+    }
 }
 
 #[derive(Eq, PartialEq, Copy, Clone)]
@@ -88,8 +170,22 @@ pub struct EditorContent<T: Default + Clone + Debug> {
     pub(super) redo_stack: Vec<EditorCommandGroup<T>>,
     pub(super) max_line_len: usize,
     pub(super) line_lens: Vec<usize>,
+    // the sum of line_lens, so the length of the content is available without iterating over the lines
+    total_line_len: usize,
     pub(super) canvas: Canvas,
     pub(super) line_data: Vec<T>,
+    // every row gets a new, unique generation whenever its content changes
+    line_generations: Vec<u64>,
+    generation: u64,
+    // the generation at the last take_changed_lines call
+    taken_generation: u64,
+    // the first row whose index changed since the last take_changed_lines call
+    first_moved_row: Option<usize>,
+    // the predominant line ending of the text passed to init_with
+    line_ending: LineEnding,
+    // the columns in each row before which the row is forced to wrap
+    soft_breaks: Vec<Vec<usize>>,
+    bookmarks: Vec<bool>,
 }
 
 impl<T: Default + Clone + Debug> EditorContent<T> {
@@ -99,7 +195,15 @@ impl<T: Default + Clone + Debug> EditorContent<T> {
             redo_stack: Vec::with_capacity(32),
             canvas: Vec::with_capacity(max_len * 64),
             line_lens: Vec::with_capacity(64),
+            total_line_len: 0,
             line_data: Vec::with_capacity(642),
+            line_generations: Vec::with_capacity(64),
+            soft_breaks: Vec::with_capacity(64),
+            bookmarks: Vec::with_capacity(64),
+            generation: 0,
+            taken_generation: 0,
+            first_moved_row: None,
+            line_ending: LineEnding::Lf,
             max_line_len: max_len,
         }
     }
@@ -116,6 +220,113 @@ impl<T: Default + Clone + Debug> EditorContent<T> {
         self.line_lens[row_i]
     }
 
+    pub fn can_insert(&self, row_i: usize, count: usize) -> bool {
+        self.line_lens[row_i] + count <= self.max_line_len
+    }
+
+    pub fn can_merge(&self, row_i: usize) -> bool {
+        row_i + 1 < self.line_count()
+            && self.line_lens[row_i] + self.line_lens[row_i + 1] <= self.max_line_len
+    }
+
+    pub fn remaining_line_capacity(&self, row_i: usize) -> usize {
+        self.max_line_len - self.line_lens[row_i]
+    }
+
+    /// The generation of a row changes whenever its content changes, so hosts can
+    /// skip the re-evaluation of rows whose generation is the same as the cached one.
+    pub fn line_generation(&self, row_index: usize) -> u64 {
+        self.line_generations[row_index]
+    }
+
+    /// Returns the (row index, content) pairs of the rows which changed, were inserted
+    /// or got a new index because a row was inserted or removed before them since the last call.
+    /// Rows from line_count() on were removed if the content had more rows before.
+    pub fn take_changed_lines(&mut self) -> Vec<(usize, String)> {
+        let taken_generation = self.taken_generation;
+        self.taken_generation = self.generation;
+        let first_moved_row = self.first_moved_row.take().unwrap_or(usize::MAX);
+        self.indexed_lines()
+            .filter(|(i, _line)| {
+                *i >= first_moved_row || self.line_generations[*i] > taken_generation
+            })
+            .map(|(i, line)| (i, line.iter().collect()))
+            .collect()
+    }
+
+    fn rows_moved_from(&mut self, row_index: usize) {
+        self.first_moved_row = Some(
+            self.first_moved_row
+                .map_or(row_index, |first| first.min(row_index)),
+        );
+    }
+
+    fn next_generation(&mut self) -> u64 {
+        self.generation += 1;
+        self.generation
+    }
+
+    fn touch_line(&mut self, row_index: usize) {
+        self.line_generations[row_index] = self.next_generation();
+        let len = self.line_lens[row_index];
+        self.soft_breaks[row_index].retain(|col| *col > 0 && *col < len);
+    }
+
+    pub fn add_soft_break(&mut self, pos: Pos) -> bool {
+        if pos.column == 0 || pos.column >= self.line_len(pos.row) {
+            return false;
+        }
+        let breaks = &mut self.soft_breaks[pos.row];
+        match breaks.binary_search(&pos.column) {
+            Ok(_) => false,
+            Err(index) => {
+                breaks.insert(index, pos.column);
+                true
+            }
+        }
+    }
+
+    /// the columns (in increasing order) before which the row is forced to wrap
+    pub fn soft_breaks(&self, row_index: usize) -> &[usize] {
+        &self.soft_breaks[row_index]
+    }
+
+    pub fn toggle_bookmark(&mut self, row_index: usize) {
+        self.bookmarks[row_index] = !self.bookmarks[row_index];
+    }
+
+    pub fn bookmarks(&self) -> Vec<usize> {
+        self.bookmarks
+            .iter()
+            .enumerate()
+            .filter(|(_row, bookmarked)| **bookmarked)
+            .map(|(row, _bookmarked)| row)
+            .collect()
+    }
+
+    /// Moves the breaks after `after_col` by `delta` columns, the breaks inside
+    /// a removed range (negative delta) are dropped.
+    fn shift_soft_breaks(&mut self, row_index: usize, after_col: usize, delta: isize) {
+        let breaks = &mut self.soft_breaks[row_index];
+        if delta < 0 {
+            let removed_to = after_col + (-delta) as usize;
+            breaks.retain(|col| *col <= after_col || *col >= removed_to);
+        }
+        for col in breaks.iter_mut().filter(|col| **col > after_col) {
+            *col = (*col as isize + delta) as usize;
+        }
+        breaks.dedup();
+    }
+
+    fn take_soft_breaks_after(&mut self, row_index: usize, after_col: usize) -> Vec<usize> {
+        let breaks = &mut self.soft_breaks[row_index];
+        let first_taken = breaks
+            .iter()
+            .position(|col| *col > after_col)
+            .unwrap_or(breaks.len());
+        breaks.split_off(first_taken)
+    }
+
     pub fn lines(&self) -> impl Iterator<Item = &[char]> {
         return self
             .canvas
@@ -124,28 +335,94 @@ impl<T: Default + Clone + Debug> EditorContent<T> {
             .map(|(line, len)| &line[0..*len]);
     }
 
+    pub fn indexed_lines(&self) -> impl Iterator<Item = (usize, &[char])> {
+        self.lines().enumerate()
+    }
+
     pub fn push_line(&mut self) {
         let line = std::iter::repeat(0 as char).take(self.max_line_len);
         self.canvas.extend(line);
         self.line_lens.push(0);
+        let generation = self.next_generation();
+        self.line_generations.push(generation);
+        self.soft_breaks.push(Vec::new());
+        self.bookmarks.push(false);
         if self.line_count() > self.line_data.len() {
             self.line_data.push(Default::default());
         }
     }
 
+    pub fn push_char(&mut self, ch: char) -> Pos {
+        let mut row = self.line_count() - 1;
+        if ch == '\n' || self.line_lens[row] == self.max_line_len {
+            self.push_line();
+            row += 1;
+        }
+        if ch != '\n' {
+            // no need to shift anything at the end of the line
+            let char_pos = self.get_char_pos(row, self.line_lens[row]);
+            self.canvas[char_pos] = ch;
+            self.set_line_len(row, self.line_lens[row] + 1);
+            self.touch_line(row);
+        }
+        Pos::from_row_column(row, self.line_lens[row])
+    }
+
     pub fn insert_line_at(&mut self, at: usize) {
         let start_pos = self.max_line_len * at;
         let line = std::iter::repeat(0 as char).take(self.max_line_len);
         self.canvas.splice(start_pos..start_pos, line);
         self.line_lens.insert(at, 0);
+        let generation = self.next_generation();
+        self.line_generations.insert(at, generation);
+        self.rows_moved_from(at);
+        self.soft_breaks.insert(at, Vec::new());
+        self.bookmarks.insert(at, false);
         self.line_data.insert(at, Default::default());
     }
 
+    pub(super) fn row_snapshot(&self, row_index: usize) -> RowSnapshot<T> {
+        RowSnapshot {
+            chars: self.get_line_valid_chars(row_index).to_vec(),
+            data: self.line_data[row_index].clone(),
+            soft_breaks: self.soft_breaks[row_index].clone(),
+            bookmarked: self.bookmarks[row_index],
+        }
+    }
+
+    /// Replaces `removed_count` rows from `first_row` with the given rows.
+    /// The new rows are inserted first, so the content never becomes empty.
+    pub(super) fn replace_rows(
+        &mut self,
+        first_row: usize,
+        removed_count: usize,
+        rows: &[RowSnapshot<T>],
+    ) {
+        for (i, row) in rows.iter().enumerate() {
+            let row_index = first_row + i;
+            self.insert_line_at(row_index);
+            let from = self.get_char_pos(row_index, 0);
+            self.canvas[from..from + row.chars.len()].copy_from_slice(&row.chars);
+            self.set_line_len(row_index, row.chars.len());
+            self.line_data[row_index] = row.data.clone();
+            self.soft_breaks[row_index] = row.soft_breaks.clone();
+            self.bookmarks[row_index] = row.bookmarked;
+        }
+        for _ in 0..removed_count {
+            self.remove_line_at(first_row + rows.len());
+        }
+    }
+
     pub fn remove_line_at(&mut self, at: usize) {
         let from = self.max_line_len * at;
         let to = from + self.max_line_len;
         self.canvas.splice(from..to, std::iter::empty());
+        self.total_line_len -= self.line_lens[at];
         self.line_lens.remove(at);
+        self.line_generations.remove(at);
+        self.rows_moved_from(at);
+        self.soft_breaks.remove(at);
+        self.bookmarks.remove(at);
         self.line_data.remove(at);
     }
 
@@ -199,7 +476,7 @@ impl<T: Default + Clone + Debug> EditorContent<T> {
 
     pub fn duplicate_line(&mut self, at: usize) {
         self.insert_line_at(at + 1);
-        self.line_lens[at + 1] = self.line_lens[at];
+        self.set_line_len(at + 1, self.line_lens[at]);
         let from = at * self.max_line_len;
         let to = from + self.line_lens[at];
         let dst = (at + 1) * self.max_line_len;
@@ -216,6 +493,13 @@ impl<T: Default + Clone + Debug> EditorContent<T> {
         &self.canvas[from..to]
     }
 
+    pub fn line_slice(&self, row: usize, cols: Range<usize>) -> String {
+        let line = self.get_line_valid_chars(row);
+        let to = cols.end.min(line.len());
+        let from = cols.start.min(to);
+        line[from..to].iter().collect()
+    }
+
     pub(super) fn get_line_chars(&self, row_index: usize) -> &[char] {
         let from = row_index * self.max_line_len;
         let to = from + self.max_line_len;
@@ -232,6 +516,14 @@ impl<T: Default + Clone + Debug> EditorContent<T> {
         return self.canvas[self.get_char_pos(row_index, column_index)];
     }
 
+    pub fn get_char_opt(&self, row_index: usize, column_index: usize) -> Option<char> {
+        if row_index >= self.line_count() || column_index >= self.line_len(row_index) {
+            None
+        } else {
+            Some(self.get_char(row_index, column_index))
+        }
+    }
+
     pub fn set_char(&mut self, row_index: usize, column_index: usize, ch: char) {
         let current_line_count = self.line_count();
         for _ in current_line_count..=row_index {
@@ -239,6 +531,7 @@ impl<T: Default + Clone + Debug> EditorContent<T> {
         }
         let char_pos = self.get_char_pos(row_index, column_index);
         self.canvas[char_pos] = ch;
+        self.touch_line(row_index);
     }
 
     pub fn insert_char(&mut self, row_index: usize, column_index: usize, ch: char) -> bool {
@@ -251,25 +544,51 @@ impl<T: Default + Clone + Debug> EditorContent<T> {
         let to = self.get_char_pos(row_index, len);
         self.canvas.copy_within(from..to, from + 1);
         self.canvas[from] = ch;
-        self.line_lens[row_index] += 1;
+        self.set_line_len(row_index, self.line_lens[row_index] + 1);
+        self.shift_soft_breaks(row_index, column_index, 1);
+        self.touch_line(row_index);
         return true;
     }
 
-    pub fn remove_char(&mut self, row_index: usize, column_index: usize) {
+    pub fn remove_char(&mut self, row_index: usize, column_index: usize) -> bool {
+        if column_index >= self.line_lens[row_index] {
+            return false;
+        }
         let from = self.get_char_pos(row_index, column_index);
         let len = self.line_lens[row_index];
         let to = self.get_char_pos(row_index, len);
         self.canvas.copy_within(from + 1..to, from);
-        self.line_lens[row_index] -= 1;
+        self.set_line_len(row_index, self.line_lens[row_index] - 1);
+        self.shift_soft_breaks(row_index, column_index, -1);
+        self.touch_line(row_index);
+        return true;
     }
 
     pub fn clear(&mut self) {
         self.line_lens.clear();
+        self.total_line_len = 0;
+        self.line_generations.clear();
+        self.soft_breaks.clear();
+        self.bookmarks.clear();
         self.undo_stack.clear();
         self.redo_stack.clear();
     }
 
+    pub fn clear_to_single_line(&mut self) {
+        self.clear();
+        self.canvas.clear();
+        self.line_data.clear();
+        self.push_line();
+    }
+
     pub fn init_with(&mut self, text: &str) {
+        let crlf_count = text.matches("\r\n").count();
+        let lf_count = text.matches('\n').count() - crlf_count;
+        self.line_ending = if crlf_count > lf_count {
+            LineEnding::CrLf
+        } else {
+            LineEnding::Lf
+        };
         self.clear();
         self.push_line();
         self.set_str_at(text, 0, 0);
@@ -281,13 +600,185 @@ impl<T: Default + Clone + Debug> EditorContent<T> {
         return result;
     }
 
-    pub fn write_content_into(&self, result: &mut String) {
+    pub fn detected_line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    pub(super) fn set_detected_line_ending(&mut self, line_ending: LineEnding) {
+        self.line_ending = line_ending;
+    }
+
+    pub fn get_content_with(&self, line_ending: LineEnding) -> String {
+        let mut result = String::with_capacity(self.canvas.len() * self.max_line_len);
+        // writing into a String never fails
+        let _ = self.write_content(&mut result, line_ending);
+        return result;
+    }
+
+    pub fn write_content<W: std::fmt::Write>(
+        &self,
+        out: &mut W,
+        line_ending: LineEnding,
+    ) -> std::fmt::Result {
         for (i, line) in self.lines().enumerate() {
             if i > 0 {
-                result.push('\n');
+                if line_ending == LineEnding::CrLf {
+                    out.write_char('\r')?;
+                }
+                out.write_char('\n')?;
+            }
+            for ch in line {
+                out.write_char(*ch)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn set_line_len(&mut self, row_index: usize, len: usize) {
+        self.total_line_len = self.total_line_len - self.line_lens[row_index] + len;
+        self.line_lens[row_index] = len;
+    }
+
+    pub fn char_count(&self) -> usize {
+        let chars_len: usize = self.line_lens.iter().sum();
+        chars_len + self.line_count() - 1
+    }
+
+    /// same as char_count but in O(1), the sum of the line lengths is maintained on every change
+    pub fn char_count_cached(&self) -> usize {
+        self.total_line_len + self.line_count() - 1
+    }
+
+    pub fn byte_len(&self) -> usize {
+        let chars_len: usize = self
+            .lines()
+            .map(|line| line.iter().map(|ch| ch.len_utf8()).sum::<usize>())
+            .sum();
+        // line breaks
+        chars_len + self.line_count() - 1
+    }
+
+    pub fn diff_against(&self, other: &str) -> Vec<RowDiff> {
+        let mut result = Vec::new();
+        for (removed, added) in self.diff_hunks(other) {
+            let changed_count = removed.len().min(added.len());
+            for (row, other_row) in removed.clone().zip(added.clone()) {
+                result.push(RowDiff::Changed { row, other_row });
             }
-            result.extend(line);
+            result.extend(removed.skip(changed_count).map(RowDiff::Removed));
+            result.extend(added.skip(changed_count).map(RowDiff::Added));
         }
+        result
+    }
+
+    /// Returns the edits which transform the content into `target`, one for every
+    /// changed block of lines. They are ordered from the end of the document
+    /// to its beginning, so they can be applied in order without adjusting their offsets.
+    pub fn edits_to_reach(&self, target: &str) -> Vec<Edit> {
+        let target_lines: Vec<&str> = target.split('\n').collect();
+        let target_text = |rows: Range<usize>| {
+            target_lines[rows]
+                .iter()
+                .map(|line| line.chars().filter(|ch| *ch != '\r').collect::<String>())
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        let line_start = |row: usize| self.pos_to_offset(Pos::from_row_column(row, 0));
+        let line_end =
+            |row: usize| self.pos_to_offset(Pos::from_row_column(row, self.line_len(row)));
+        let line_count = self.line_count();
+        let mut edits: Vec<Edit> = self
+            .diff_hunks(target)
+            .into_iter()
+            .map(|(removed, added)| {
+                if removed.is_empty() {
+                    // pure insertion, before the row or after the last one
+                    if removed.start < line_count {
+                        Edit {
+                            offset: line_start(removed.start),
+                            removed_len: 0,
+                            text: target_text(added) + "\n",
+                        }
+                    } else {
+                        Edit {
+                            offset: line_end(line_count - 1),
+                            removed_len: 0,
+                            text: "\n".to_owned() + &target_text(added),
+                        }
+                    }
+                } else if added.is_empty() {
+                    // pure deletion, together with the line break after (or before) the rows
+                    let (from, to) = if removed.end < line_count {
+                        (line_start(removed.start), line_start(removed.end))
+                    } else {
+                        (line_end(removed.start - 1), line_end(removed.end - 1))
+                    };
+                    Edit {
+                        offset: from,
+                        removed_len: to - from,
+                        text: String::new(),
+                    }
+                } else {
+                    let from = line_start(removed.start);
+                    Edit {
+                        offset: from,
+                        removed_len: line_end(removed.end - 1) - from,
+                        text: target_text(added),
+                    }
+                }
+            })
+            .collect();
+        edits.reverse();
+        edits
+    }
+
+    fn diff_hunks(&self, other: &str) -> Vec<(Range<usize>, Range<usize>)> {
+        let lines: Vec<&[char]> = self.lines().collect();
+        let other_lines: Vec<Vec<char>> = other
+            .split('\n')
+            .map(|line| line.chars().filter(|ch| *ch != '\r').collect())
+            .collect();
+        let n = lines.len();
+        let m = other_lines.len();
+        // lcs[i][j] is the length of the LCS of lines[i..] and other_lines[j..]
+        let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i][j] = if lines[i] == other_lines[j].as_slice() {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+
+        let mut result = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        let (mut hunk_i, mut hunk_j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && lines[i] == other_lines[j].as_slice() {
+                if hunk_i < i || hunk_j < j {
+                    result.push((hunk_i..i, hunk_j..j));
+                }
+                i += 1;
+                j += 1;
+                hunk_i = i;
+                hunk_j = j;
+            } else if j < m && (i == n || lcs[i][j + 1] >= lcs[i + 1][j]) {
+                j += 1;
+            } else {
+                i += 1;
+            }
+        }
+        if hunk_i < i || hunk_j < j {
+            result.push((hunk_i..i, hunk_j..j));
+        }
+        result
+    }
+
+    pub fn write_content_into(&self, result: &mut String) {
+        // writing into a String never fails
+        let _ = self.write_content(result, LineEnding::Lf);
     }
 
     pub fn set_str_at(&mut self, str: &str, row_index: usize, insert_at: usize) -> Pos {
@@ -298,14 +789,14 @@ impl<T: Default + Clone + Debug> EditorContent<T> {
                 // ignore
                 continue;
             } else if ch == '\n' {
-                self.line_lens[row] = col;
+                self.set_line_len(row, col);
                 debug_assert!(self.line_lens[row] <= self.max_line_len);
                 row += 1;
                 self.insert_line_at(row);
                 col = 0;
                 continue;
             } else if col == self.max_line_len {
-                self.line_lens[row] = col;
+                self.set_line_len(row, col);
                 debug_assert!(self.line_lens[row] <= self.max_line_len);
                 row += 1;
                 self.insert_line_at(row);
@@ -314,24 +805,47 @@ impl<T: Default + Clone + Debug> EditorContent<T> {
             self.set_char(row, col, ch);
             col += 1;
         }
-        self.line_lens[row] = col;
+        self.set_line_len(row, col);
         debug_assert!(self.line_lens[row] <= self.max_line_len);
+        self.touch_line(row);
         return Pos::from_row_column(row, col);
     }
 
+    /// Converts a char offset into the content (where every line break counts as one char)
+    /// into a position. Offsets beyond the end are clamped to the end of the content.
+    pub fn offset_to_pos(&self, offset: usize) -> Pos {
+        let mut remaining = offset;
+        for (row, len) in self.line_lens.iter().enumerate() {
+            if remaining <= *len {
+                return Pos::from_row_column(row, remaining);
+            }
+            remaining -= len + 1;
+        }
+        let last_row = self.line_count() - 1;
+        Pos::from_row_column(last_row, self.line_len(last_row))
+    }
+
+    pub fn pos_to_offset(&self, pos: Pos) -> usize {
+        let preceding_rows: usize = self.line_lens[0..pos.row].iter().map(|len| len + 1).sum();
+        preceding_rows + pos.column
+    }
+
     pub fn split_line(&mut self, row_index: usize, split_at: usize) {
+        let moved_breaks = self.take_soft_breaks_after(row_index, split_at);
         self.insert_line_at(row_index + 1);
+        self.soft_breaks[row_index + 1] = moved_breaks.iter().map(|col| col - split_at).collect();
         let new_line_pos = self.get_char_pos(row_index + 1, 0);
 
         {
             let from = self.get_char_pos(row_index, split_at);
             let to = self.get_char_pos(row_index, self.line_lens[row_index]);
             self.canvas.copy_within(from..to, new_line_pos);
-            self.line_lens[row_index + 1] = to - from;
+            self.set_line_len(row_index + 1, to - from);
             debug_assert!(self.line_lens[row_index + 1] <= self.max_line_len);
         }
-        self.line_lens[row_index] = split_at;
+        self.set_line_len(row_index, split_at);
         debug_assert!(self.line_lens[row_index] <= self.max_line_len);
+        self.touch_line(row_index);
     }
 
     pub fn merge_with_next_row(
@@ -359,8 +873,16 @@ impl<T: Default + Clone + Debug> EditorContent<T> {
                 return false;
             }
             self.canvas.copy_within(src_from..src_to, dst);
-            self.line_lens[row_index] = new_line_len;
+            self.set_line_len(row_index, new_line_len);
             debug_assert!(self.line_lens[row_index] <= self.max_line_len);
+            self.take_soft_breaks_after(row_index, first_row_col);
+            let appended_breaks = self.take_soft_breaks_after(row_index + 1, second_row_col);
+            self.soft_breaks[row_index].extend(
+                appended_breaks
+                    .iter()
+                    .map(|col| first_row_col + (col - second_row_col)),
+            );
+            self.touch_line(row_index);
             self.remove_line_at(row_index + 1);
         }
 
@@ -388,16 +910,20 @@ impl<T: Default + Clone + Debug> EditorContent<T> {
                 ));
                 self.remove_line_at(first.row);
                 Some(RowModificationType::AllLinesFrom(first.row))
-            } else if self.merge_with_next_row(first.row, first.column, second.column) {
-                Some(RowModificationType::AllLinesFrom(first.row))
             } else {
-                None
+                // the intervening rows are already removed, so the merge must not fail,
+                // the merged length was checked above
+                let merged = self.merge_with_next_row(first.row, first.column, second.column);
+                debug_assert!(merged);
+                Some(RowModificationType::AllLinesFrom(first.row))
             }
         } else {
             self.get_mut_line_chars(first.row)
                 .copy_within(second.column.., first.column);
             let selected_char_count = second.column - first.column;
-            self.line_lens[first.row] -= selected_char_count;
+            self.set_line_len(first.row, self.line_lens[first.row] - selected_char_count);
+            self.shift_soft_breaks(first.row, first.column, -(selected_char_count as isize));
+            self.touch_line(first.row);
             Some(RowModificationType::SingleLine(first.row))
         };
     }
@@ -413,6 +939,8 @@ impl<T: Default + Clone + Debug> EditorContent<T> {
             ch.encode_utf8(&mut text_to_move_buf[text_to_move_buf_index..]);
             text_to_move_buf_index += ch.len_utf8();
         }
+        // the breaks of the moved text are put back after it was moved
+        let moved_breaks = self.take_soft_breaks_after(pos.row, pos.column);
 
         let new_pos = self.set_str_at(&str, pos.row, pos.column);
         if text_to_move_buf_index > 0 {
@@ -423,50 +951,398 @@ impl<T: Default + Clone + Debug> EditorContent<T> {
                 new_pos.row,
                 new_pos.column,
             );
-            self.line_lens[p.row] = p.column;
+            self.set_line_len(p.row, p.column);
             debug_assert!(self.line_lens[p.row] <= self.max_line_len);
         }
+        self.touch_line(pos.row);
+        for col in moved_breaks {
+            // the moved text is wrapped the same way as in set_str_at
+            let moved_col = new_pos.column + (col - pos.column);
+            self.add_soft_break(Pos::from_row_column(
+                new_pos.row + moved_col / self.max_line_len,
+                moved_col % self.max_line_len,
+            ));
+        }
         return (new_pos, text_to_move_buf_index > 0);
     }
 
+    pub fn indent_level(&self, row: usize, tab_width: usize) -> usize {
+        let tab_width = tab_width.max(1);
+        let mut level = 0;
+        for ch in self.get_line_valid_chars(row) {
+            match ch {
+                ' ' => level += 1,
+                '\t' => level = (level / tab_width + 1) * tab_width,
+                _ => break,
+            }
+        }
+        level
+    }
+
+    /// returns the (inclusive) range of the rows following `row` which are indented
+    /// more deeply than `row`, or None if it has no such children.
+    /// Blank lines inside the range are part of it.
+    pub fn fold_range(&self, row: usize, tab_width: usize) -> Option<(usize, usize)> {
+        if self.is_blank_line(row) {
+            return None;
+        }
+        let level = self.indent_level(row, tab_width);
+        let mut last_child_row = None;
+        for i in row + 1..self.line_count() {
+            if self.is_blank_line(i) {
+                continue;
+            } else if self.indent_level(i, tab_width) > level {
+                last_child_row = Some(i);
+            } else {
+                break;
+            }
+        }
+        last_child_row.map(|last| (row + 1, last))
+    }
+
+    fn is_blank_line(&self, row: usize) -> bool {
+        self.get_line_valid_chars(row)
+            .iter()
+            .all(|ch| ch.is_ascii_whitespace())
+    }
+
+    pub fn number_literals(&self) -> Vec<(Selection, f64)> {
+        let mut result = Vec::new();
+        for (row, line) in self.lines().enumerate() {
+            let mut col = 0;
+            while col < line.len() {
+                let is_token_start =
+                    col == 0 || !(line[col - 1].is_alphanumeric() || line[col - 1] == '_');
+                if !is_token_start || !line[col].is_ascii_digit() {
+                    col += 1;
+                    continue;
+                }
+                let start = col;
+                let radix = match line.get(col + 1) {
+                    Some('x') if line[col] == '0' => 16,
+                    Some('b') if line[col] == '0' => 2,
+                    _ => 10,
+                };
+                let value = if radix == 10 {
+                    while col < line.len() && line[col].is_ascii_digit() {
+                        col += 1;
+                    }
+                    if col + 1 < line.len() && line[col] == '.' && line[col + 1].is_ascii_digit() {
+                        col += 1;
+                        while col < line.len() && line[col].is_ascii_digit() {
+                            col += 1;
+                        }
+                    }
+                    line[start..col]
+                        .iter()
+                        .collect::<String>()
+                        .parse::<f64>()
+                        .ok()
+                } else {
+                    col += 2;
+                    let digits_start = col;
+                    while col < line.len() && line[col].is_digit(radix) {
+                        col += 1;
+                    }
+                    let digits: String = line[digits_start..col].iter().collect();
+                    u64::from_str_radix(&digits, radix).ok().map(|it| it as f64)
+                };
+                // e.g. "12abc" or "0xFFz" are not numbers
+                let is_token_end =
+                    col == line.len() || !(line[col].is_alphanumeric() || line[col] == '_');
+                if let (Some(value), true) = (value, is_token_end) {
+                    result.push((
+                        Selection::range(
+                            Pos::from_row_column(row, start),
+                            Pos::from_row_column(row, col),
+                        ),
+                        value,
+                    ));
+                }
+                while col < line.len() && (line[col].is_alphanumeric() || line[col] == '_') {
+                    col += 1;
+                }
+            }
+        }
+        result
+    }
+
+    pub fn unbalanced_brackets(&self) -> Vec<Pos> {
+        let mut unmatched = Vec::new();
+        let mut openers: Vec<(char, Pos)> = Vec::new();
+        for (row, line) in self.lines().enumerate() {
+            for (column, ch) in line.iter().enumerate() {
+                let pos = Pos::from_row_column(row, column);
+                let expected_opener = match ch {
+                    '(' | '[' | '{' => {
+                        openers.push((*ch, pos));
+                        continue;
+                    }
+                    ')' => '(',
+                    ']' => '[',
+                    '}' => '{',
+                    _ => continue,
+                };
+                match openers.last() {
+                    Some((opener, _)) if *opener == expected_opener => {
+                        openers.pop();
+                    }
+                    _ => unmatched.push(pos),
+                }
+            }
+        }
+        unmatched.extend(openers.iter().map(|(_, pos)| *pos));
+        unmatched.sort_by_key(|pos| (pos.row, pos.column));
+        unmatched
+    }
+
+    pub fn matching_bracket(&self, pos: Pos) -> Option<Pos> {
+        let (opener, closer, forward) = match self.get_char_opt(pos.row, pos.column)? {
+            '(' => ('(', ')', true),
+            '[' => ('[', ']', true),
+            '{' => ('{', '}', true),
+            ')' => ('(', ')', false),
+            ']' => ('[', ']', false),
+            '}' => ('{', '}', false),
+            _ => return None,
+        };
+        let mut depth = 0;
+        let mut row = pos.row;
+        let mut col = pos.column;
+        loop {
+            let line = self.get_line_valid_chars(row);
+            let ch = line[col];
+            if ch == opener {
+                depth += if forward { 1 } else { -1 };
+            } else if ch == closer {
+                depth += if forward { -1 } else { 1 };
+            }
+            if depth == 0 {
+                return Some(Pos::from_row_column(row, col));
+            }
+            if forward {
+                col += 1;
+                while col >= self.line_len(row) {
+                    row += 1;
+                    col = 0;
+                    if row >= self.line_count() {
+                        return None;
+                    }
+                }
+            } else {
+                while col == 0 {
+                    if row == 0 {
+                        return None;
+                    }
+                    row -= 1;
+                    col = self.line_len(row);
+                }
+                col -= 1;
+            }
+        }
+    }
+
+    /// returns how many chars from `start_col` fit into `width` without breaking a word.
+    /// If a single word is longer than `width`, it is broken at `width`.
+    pub fn wrap_segment_at(&self, row: usize, start_col: usize, width: usize) -> usize {
+        let len = self.line_len(row);
+        if start_col >= len {
+            return 0;
+        }
+        let remaining = len - start_col;
+        if remaining <= width {
+            return remaining;
+        }
+        let line = self.get_line_valid_chars(row);
+        let end = start_col + width;
+        if line[end].is_ascii_whitespace() {
+            return width;
+        }
+        let mut col = end;
+        while col > start_col {
+            if line[col - 1].is_ascii_whitespace() {
+                return col - start_col;
+            }
+            col -= 1;
+        }
+        width
+    }
+
+    pub fn same_visual_row(&self, a: Pos, b: Pos, width: usize) -> bool {
+        a.row == b.row && self.wrap_segment_index(a, width) == self.wrap_segment_index(b, width)
+    }
+
+    pub fn wrapped_segment_text(&self, row: usize, segment: usize, width: usize) -> String {
+        let width = width.max(1);
+        let len = self.line_len(row);
+        let mut col = 0;
+        for _ in 0..segment {
+            col += self.wrap_segment_at(row, col, width);
+            if col >= len {
+                return String::new();
+            }
+        }
+        let segment_len = self.wrap_segment_at(row, col, width);
+        self.line_slice(row, col..col + segment_len)
+    }
+
+    /// the index of the wrapped segment of the row which contains the position,
+    /// a position at the boundary of two segments belongs to the latter
+    fn wrap_segment_index(&self, pos: Pos, width: usize) -> usize {
+        let width = width.max(1);
+        let len = self.line_len(pos.row);
+        let mut col = 0;
+        let mut index = 0;
+        loop {
+            col += self.wrap_segment_at(pos.row, col, width);
+            if col >= len || pos.column < col {
+                return index;
+            }
+            index += 1;
+        }
+    }
+
+    /// Maps an index of the flattened visual cells (visual_row * width + visual_column)
+    /// to the logical position, wrapping the lines at `width`.
+    /// Indices after the end of a visual row snap to its end, indices after the
+    /// last visual row snap to the end of the document.
+    pub fn display_index_to_pos(&self, display_index: usize, width: usize) -> Pos {
+        let width = width.max(1);
+        let target_visual_row = display_index / width;
+        let target_visual_col = display_index % width;
+        let mut visual_row = 0;
+        for row in 0..self.line_count() {
+            let len = self.line_len(row);
+            let mut col = 0;
+            loop {
+                let segment_len = self.wrap_segment_at(row, col, width);
+                if visual_row == target_visual_row {
+                    // the cursor can't stand after the last char of a wrapped segment
+                    let max_col = if col + segment_len < len {
+                        col + segment_len - 1
+                    } else {
+                        len
+                    };
+                    return Pos::from_row_column(row, (col + target_visual_col).min(max_col));
+                }
+                visual_row += 1;
+                col += segment_len;
+                if col >= len {
+                    break;
+                }
+            }
+        }
+        let last_row = self.line_count() - 1;
+        Pos::from_row_column(last_row, self.line_len(last_row))
+    }
+
+    /// Maps a display column (where wide chars occupy two cells) to a char column.
+    /// A display column in the second cell of a wide char snaps to its right edge.
+    pub fn display_col_to_char_col(&self, row: usize, display_col: usize) -> usize {
+        let mut cell = 0;
+        for (col, ch) in self.get_line_valid_chars(row).iter().enumerate() {
+            let width = char_display_width(*ch);
+            if display_col < cell + width {
+                return if display_col > cell { col + 1 } else { col };
+            }
+            cell += width;
+        }
+        self.line_len(row)
+    }
+
+    pub fn line_display_width(&self, row: usize) -> usize {
+        self.get_line_valid_chars(row)
+            .iter()
+            .map(|ch| char_display_width(*ch))
+            .sum()
+    }
+
     pub fn swap_lines_upward(&mut self, lower_row: usize) {
+        self.swap_lines(lower_row - 1, lower_row);
+    }
+
+    pub fn swap_lines(&mut self, a: usize, b: usize) {
+        if a == b {
+            return;
+        }
+        let upper_row = a.min(b);
+        let lower_row = a.max(b);
         let maxlen = self.max_line_len();
         // swap lines
         {
-            let upper_i = self.get_char_pos(lower_row - 1, 0);
+            let upper_i = self.get_char_pos(upper_row, 0);
             let cur_i = self.get_char_pos(lower_row, 0);
             let (left, right) = self.canvas.split_at_mut(cur_i);
-            left[upper_i..].swap_with_slice(&mut right[0..maxlen]);
+            left[upper_i..upper_i + maxlen].swap_with_slice(&mut right[0..maxlen]);
+        }
+        self.line_lens.swap(upper_row, lower_row);
+        // both rows have a new content at their index
+        self.line_generations[upper_row] = self.next_generation();
+        self.line_generations[lower_row] = self.next_generation();
+        self.soft_breaks.swap(upper_row, lower_row);
+        self.bookmarks.swap(upper_row, lower_row);
+        self.line_data.swap(upper_row, lower_row);
+    }
+
+    pub fn word_ranges_in_line(&self, row: usize) -> Vec<Range<usize>> {
+        let is_word_char = |ch: char| ch.is_alphanumeric() || ch == '_';
+        let line = self.get_line_valid_chars(row);
+        let mut ranges = Vec::with_capacity(8);
+        let mut col = 0;
+        while col < line.len() {
+            if is_word_char(line[col]) {
+                let start = col;
+                while col < line.len() && is_word_char(line[col]) {
+                    col += 1;
+                }
+                ranges.push(start..col);
+            } else {
+                col += 1;
+            }
+        }
+        ranges
+    }
+
+    pub fn word_frequencies(&self, case_insensitive: bool) -> HashMap<String, usize> {
+        let mut frequencies = HashMap::new();
+        for row in 0..self.line_count() {
+            let line = self.get_line_valid_chars(row);
+            for range in self.word_ranges_in_line(row) {
+                let word: String = line[range].iter().collect();
+                let word = if case_insensitive {
+                    word.to_lowercase()
+                } else {
+                    word
+                };
+                *frequencies.entry(word).or_insert(0) += 1;
+            }
         }
-        let tmp = self.line_lens[lower_row - 1];
-        self.line_lens[lower_row - 1] = self.line_lens[lower_row];
-        self.line_lens[lower_row] = tmp;
+        frequencies
+    }
 
-        let tmp = std::mem::replace(&mut self.line_data[lower_row - 1], Default::default());
-        self.line_data[lower_row - 1] = std::mem::replace(&mut self.line_data[lower_row], tmp);
+    pub fn prev_word_boundary(&self, pos: Pos) -> usize {
+        self.jump_word_backward(&pos, JumpMode::IgnoreWhitespaces)
+    }
+
+    pub fn next_word_boundary(&self, pos: Pos) -> usize {
+        self.jump_word_forward(&pos, JumpMode::IgnoreWhitespaces)
+    }
+
+    pub fn char_class_at(&self, pos: Pos) -> Option<CharClass> {
+        self.get_char_opt(pos.row, pos.column).map(CharClass::of)
     }
 
     pub fn jump_word_backward(&self, cur_pos: &Pos, mode: JumpMode) -> usize {
         let mut col = cur_pos.column;
         let line = self.get_line_chars(cur_pos.row);
         while col > 0 {
-            if line[col - 1].is_alphanumeric() || line[col - 1] == '_' {
-                col -= 1;
-                while col > 0 && (line[col - 1].is_alphanumeric() || line[col - 1] == '_') {
-                    col -= 1;
-                }
-                break;
-            } else if line[col - 1] == '\"' {
+            let class = CharClass::of(line[col - 1]);
+            if class == CharClass::Quote {
                 col -= 1;
                 break;
-            } else if !line[col - 1].is_ascii_whitespace() {
+            } else if class != CharClass::Whitespace {
                 col -= 1;
-                while col > 0
-                    && !(line[col - 1].is_alphanumeric()
-                        || line[col - 1] == '_'
-                        || line[col - 1] == '\"'
-                        || line[col - 1].is_ascii_whitespace())
-                {
+                while col > 0 && CharClass::of(line[col - 1]) == class {
                     col -= 1;
                 }
                 break;
@@ -497,23 +1373,13 @@ impl<T: Default + Clone + Debug> EditorContent<T> {
         let line = self.get_line_chars(cur_pos.row);
         let len = self.line_len(cur_pos.row);
         while col < len {
-            if line[col].is_alphanumeric() || line[col] == '_' {
-                col += 1;
-                while col < len && (line[col].is_alphanumeric() || line[col] == '_') {
-                    col += 1;
-                }
-                break;
-            } else if line[col] == '\"' {
+            let class = CharClass::of(line[col]);
+            if class == CharClass::Quote {
                 col += 1;
                 break;
-            } else if !line[col].is_ascii_whitespace() {
+            } else if class != CharClass::Whitespace {
                 col += 1;
-                while col < len
-                    && !(line[col].is_alphanumeric()
-                        || line[col] == '_'
-                        || line[col] == '\"'
-                        || line[col].is_ascii_whitespace())
-                {
+                while col < len && CharClass::of(line[col]) == class {
                     col += 1;
                 }
                 break;
@@ -538,3 +1404,23 @@ impl<T: Default + Clone + Debug> EditorContent<T> {
         col
     }
 }
+
+pub fn char_display_width(ch: char) -> usize {
+    match ch as u32 {
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
@@ -1,3 +1,5 @@
 pub mod editor;
 pub mod editor_content;
 pub mod test;
+#[cfg(test)]
+pub(crate) mod testing;
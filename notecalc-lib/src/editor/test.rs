@@ -1,14 +1,17 @@
 #[cfg(test)]
 mod tests {
     use crate::editor::editor::{
-        Editor, EditorInputEvent, InputModifiers, Pos, RowModificationType, Selection,
+        CursorState, DragMode, Editor, EditorInputEvent, InputEffect, InputModifiers,
+        OverflowPolicy, Pos, RowModificationType, Selection, SelectionSummary, ViewState,
+        EDITOR_CURSOR_TICK_MS,
+    };
+    use crate::editor::editor_content::{
+        CharClass, Edit, EditorCommand, EditorContent, LineEnding, RowDiff, RowSnapshot,
+        RowsSnapshot,
+    };
+    use crate::editor::testing::{
+        assert_content_markup, assert_markup, assert_selection, build, load_markup,
     };
-    use crate::editor::editor_content::EditorContent;
-
-    const CURSOR_MARKER: char = '█';
-    // U+2770	❰	e2 9d b0	HEAVY LEFT-POINTING ANGLE BRACKET OR­NA­MENT
-    const SELECTION_START_MARK: char = '❱';
-    const SELECTION_END_MARK: char = '❰';
 
     #[derive(Clone)]
     struct TestParams2<'a> {
@@ -126,40 +129,7 @@ mod tests {
         content: &mut EditorContent<usize>,
         params: TestParams,
     ) -> Vec<Option<RowModificationType>> {
-        // we can assume here that it does not contain illegal or complex input
-        // so we can just set it as it is
-        let mut selection_found = false;
-        let mut selection_start = Pos { row: 0, column: 0 };
-        let mut selection_end = Pos { row: 0, column: 0 };
-        for (row_index, line) in params.initial_content.lines().enumerate() {
-            let mut row_len = 0;
-            for char in line.chars() {
-                if char == CURSOR_MARKER {
-                    editor.set_cursor_pos_r_c(row_index, row_len);
-                } else if char == SELECTION_START_MARK {
-                    selection_found = true;
-                    selection_start = Pos {
-                        row: row_index,
-                        column: row_len,
-                    };
-                } else if char == SELECTION_END_MARK {
-                    selection_end = Pos {
-                        row: row_index,
-                        column: row_len,
-                    };
-                } else {
-                    content.set_char(row_index, row_len, char);
-                    row_len += 1;
-                }
-            }
-            if content.line_lens.len() <= row_index {
-                content.push_line();
-            }
-            content.line_lens[row_index] = row_len;
-        }
-        if selection_found {
-            editor.set_cursor_range(selection_start, selection_end);
-        }
+        load_markup(editor, content, params.initial_content);
 
         if let Some(text) = params.text_input {
             editor.insert_text_undoable(text, content);
@@ -191,83 +161,15 @@ mod tests {
 
         // assert
         let editor: &Editor = editor;
-        let mut expected_cursor = Selection::single_r_c(0, 0);
-        let mut expected_selection_start = Pos { row: 0, column: 0 };
-        let mut expected_selection_end = Pos { row: 0, column: 0 };
-        let mut selection_found = false;
-        for (row_index, expected_line) in params.expected_content.lines().enumerate() {
-            let mut expected_row_len = 0;
-            for char in expected_line.chars() {
-                if char == CURSOR_MARKER {
-                    expected_cursor = Selection::single_r_c(row_index, expected_row_len);
-                } else if char == SELECTION_START_MARK {
-                    selection_found = true;
-                    expected_selection_start = Pos {
-                        row: row_index,
-                        column: expected_row_len,
-                    }
-                } else if char == SELECTION_END_MARK {
-                    expected_selection_end = Pos {
-                        row: row_index,
-                        column: expected_row_len,
-                    }
-                } else {
-                    assert_eq!(
-                        content.get_line_chars(row_index)[expected_row_len],
-                        char,
-                        "row: {}, column: {}, chars: {:?}",
-                        row_index,
-                        expected_row_len,
-                        content
-                            .get_line_chars(row_index)
-                            .iter()
-                            .take_while(|it| **it as u8 != 0)
-                            .collect::<String>()
-                    );
-                    expected_row_len += 1;
-                }
-            }
-
-            assert_eq!(
-                params.expected_content.lines().count(),
-                content.line_count(),
-                "Expected line count differs. Current content: {}",
-                content.get_content()
-            );
-            assert!(
-                content.line_lens[row_index] <= expected_row_len,
-                "Line {}, Actual data is longer: {:?}",
-                row_index,
-                &content.get_line_chars(row_index)[expected_row_len..content.line_lens[row_index]]
-            );
-            assert!(
-                content.line_lens[row_index] >= expected_row_len,
-                "Line {}, Actual data is shorter,  actual: {:?} \n, expected: {:?}",
-                row_index,
-                &content.get_line_chars(row_index)[0..content.line_lens[row_index]],
-                &expected_line[content.line_lens[row_index]..expected_row_len]
-            );
-        }
-        if selection_found {
-            assert_eq!(
-                editor.get_selection().start,
-                expected_selection_start,
-                "Selection start"
-            );
-            assert!(editor.get_selection().is_range());
-            assert_eq!(
-                editor.get_selection().end.unwrap(),
-                expected_selection_end,
-                "Selection end"
-            );
-        } else if !expected_cursor.is_range() && params.undo_count > 0 {
+        let expected_selection = assert_content_markup(content, params.expected_content);
+        if !expected_selection.is_range() && params.undo_count > 0 {
             assert_eq!(
                 editor.get_selection().start.row,
-                expected_cursor.start.row,
+                expected_selection.start.row,
                 "The cursor is not reverted back during undo"
             );
         } else {
-            assert_eq!(editor.get_selection(), expected_cursor, "Cursor");
+            assert_selection(editor, expected_selection);
         }
         return modification_types;
     }
@@ -6197,4 +6099,3754 @@ interest rate / (12 (1/year))
 
         assert_eq!(editor.clipboard, "aaaaaaaaaa\n".to_owned());
     }
+
+    #[test]
+    fn test_transpose_lines_in_the_middle() {
+        let (mut editor, mut content) = build("first\nsecond\nthi█rd\nfourth");
+
+        assert_eq!(
+            editor.transpose_lines_undoable(&mut content),
+            Some(RowModificationType::AllLinesFrom(1))
+        );
+        assert_markup(&editor, &content, "first\nthird\nsecond\nfou█rth");
+
+        editor.undo(&mut content);
+        assert_eq!(content.get_content(), "first\nsecond\nthird\nfourth");
+    }
+
+    #[test]
+    fn test_transpose_lines_on_the_first_line_is_noop() {
+        let (mut editor, mut content) = build("fi█rst\nsecond");
+
+        assert_eq!(editor.transpose_lines_undoable(&mut content), None);
+        assert_markup(&editor, &content, "fi█rst\nsecond");
+        assert!(content.undo_stack.is_empty());
+    }
+
+    #[test]
+    fn test_transpose_lines_with_two_line_selection() {
+        let (mut editor, mut content) = build("first\nse❱cond\nt❰hird");
+
+        editor.transpose_lines_undoable(&mut content);
+        assert_markup(&editor, &content, "first\n❱third\nsecond❰");
+    }
+
+    #[test]
+    fn test_wrap_segment_at() {
+        let (_editor, content) = build("lorem ipsum dolor");
+
+        // everything fits
+        assert_eq!(content.wrap_segment_at(0, 0, 20), 17);
+        assert_eq!(content.wrap_segment_at(0, 12, 10), 5);
+        // the segment ends right before a space
+        assert_eq!(content.wrap_segment_at(0, 0, 5), 5);
+        // "dolor" does not fit, break after "ipsum "
+        assert_eq!(content.wrap_segment_at(0, 0, 14), 12);
+        assert_eq!(content.wrap_segment_at(0, 6, 8), 6);
+        // a single word is too long
+        assert_eq!(content.wrap_segment_at(0, 0, 3), 3);
+        assert_eq!(content.wrap_segment_at(0, 17, 3), 0);
+    }
+
+    #[test]
+    fn test_click_snaps_to_the_nearest_edge_of_wide_chars() {
+        let (mut editor, content) = build("a漢b");
+        editor.set_wide_char_aware_click(true);
+
+        // first cell of '漢'
+        editor.handle_click(1, 0, &content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 1));
+        // second cell of '漢'
+        editor.handle_click(2, 0, &content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 2));
+        // 'b'
+        editor.handle_click(3, 0, &content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 2));
+        // after the end of the line
+        editor.handle_click(10, 0, &content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 3));
+    }
+
+    #[test]
+    fn test_click_without_wide_char_awareness_uses_char_columns() {
+        let (mut editor, content) = build("a漢b");
+
+        editor.handle_click(2, 0, &content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 2));
+    }
+
+    #[test]
+    fn test_delete_to_forward() {
+        let (mut editor, mut content) = build("first█ line\nsecond line\nthird line");
+
+        assert_eq!(
+            editor.delete_to(Pos::from_row_column(1, 6), &mut content),
+            Some(RowModificationType::AllLinesFrom(0))
+        );
+        assert_markup(&editor, &content, "first█ line\nthird line");
+
+        editor.undo(&mut content);
+        assert_eq!(content.get_content(), "first line\nsecond line\nthird line");
+    }
+
+    #[test]
+    fn test_delete_to_backward() {
+        let (mut editor, mut content) = build("first line\nsecond█ line");
+
+        assert_eq!(
+            editor.delete_to(Pos::from_row_column(1, 0), &mut content),
+            Some(RowModificationType::SingleLine(1))
+        );
+        assert_markup(&editor, &content, "first line\n█ line");
+    }
+
+    #[test]
+    fn test_delete_to_clamps_the_target() {
+        let (mut editor, mut content) = build("first line\nsecond█ line");
+
+        editor.delete_to(Pos::from_row_column(10, 50), &mut content);
+        assert_markup(&editor, &content, "first line\nsecond█");
+
+        assert_eq!(
+            editor.delete_to(Pos::from_row_column(1, 6), &mut content),
+            None
+        );
+    }
+
+    #[test]
+    fn test_last_input_effect() {
+        let (mut editor, mut content) = build("abc\ndef");
+        assert_eq!(editor.last_input_effect(), InputEffect::NoChange);
+
+        editor.handle_input_undoable(
+            EditorInputEvent::Right,
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(editor.last_input_effect(), InputEffect::SelectionChanged);
+
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('x'),
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(editor.last_input_effect(), InputEffect::TextChanged(0));
+
+        editor.handle_input_undoable(
+            EditorInputEvent::PageDown,
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(editor.last_input_effect(), InputEffect::SelectionChanged);
+        // Right at the end of the document does nothing
+        editor.handle_input_undoable(
+            EditorInputEvent::Right,
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(editor.last_input_effect(), InputEffect::NoChange);
+    }
+
+    #[test]
+    fn test_paste_long_line_with_wrap_policy() {
+        let (mut editor, mut content) = build("");
+
+        assert_eq!(
+            editor.paste(&"a".repeat(200), OverflowPolicy::Wrap, &mut content),
+            Some(RowModificationType::AllLinesFrom(0))
+        );
+        assert_eq!(content.line_count(), 3);
+        assert_eq!(content.line_len(0), 80);
+        assert_eq!(content.line_len(1), 80);
+        assert_eq!(content.line_len(2), 40);
+    }
+
+    #[test]
+    fn test_paste_long_line_with_truncate_policy() {
+        let (mut editor, mut content) = build("x█y\nend");
+
+        editor.paste(
+            &("a".repeat(200) + "\n" + &"b".repeat(200)),
+            OverflowPolicy::Truncate,
+            &mut content,
+        );
+        assert_eq!(content.line_count(), 3);
+        assert_eq!(
+            content.get_content().lines().next().unwrap(),
+            "x".to_owned() + &"a".repeat(79)
+        );
+        assert_eq!(
+            content.get_content().lines().nth(1).unwrap(),
+            "b".repeat(79) + "y"
+        );
+        assert_eq!(content.get_content().lines().nth(2).unwrap(), "end");
+        assert_eq!(editor.get_selection(), Selection::single_r_c(1, 79));
+    }
+
+    #[test]
+    fn test_paste_long_line_with_reject_policy() {
+        let (mut editor, mut content) = build("x█y");
+
+        assert_eq!(
+            editor.paste(&"a".repeat(200), OverflowPolicy::Reject, &mut content),
+            None
+        );
+        assert_markup(&editor, &content, "x█y");
+        assert!(content.undo_stack.is_empty());
+
+        // it fits exactly
+        assert!(editor
+            .paste(&"a".repeat(78), OverflowPolicy::Reject, &mut content)
+            .is_some());
+        assert_eq!(content.line_len(0), 80);
+    }
+
+    #[test]
+    fn test_set_selection_by_offsets_single_line() {
+        let (mut editor, content) = build("first\nsecond line");
+
+        editor.set_selection_by_offsets(8, 11, &content);
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(1, 2), Pos::from_row_column(1, 5))
+        );
+
+        editor.set_selection_by_offsets(3, 3, &content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 3));
+    }
+
+    #[test]
+    fn test_set_selection_by_offsets_cross_line() {
+        let (mut editor, content) = build("first\nsecond line\nthird");
+
+        editor.set_selection_by_offsets(5, 6, &content);
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(0, 5), Pos::from_row_column(1, 0))
+        );
+
+        editor.set_selection_by_offsets(2, 20, &content);
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(0, 2), Pos::from_row_column(2, 2))
+        );
+
+        // out of range offsets are clamped
+        editor.set_selection_by_offsets(20, 1000, &content);
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(2, 2), Pos::from_row_column(2, 5))
+        );
+    }
+
+    #[test]
+    fn test_insert_text_selected_single_line() {
+        let (mut editor, mut content) = build("sum = █");
+
+        editor.insert_text_selected("value", &mut content);
+        assert_markup(&editor, &content, "sum = ❱value❰");
+
+        // overtyping replaces the inserted text
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('1'),
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(content.get_content(), "sum = 1");
+    }
+
+    #[test]
+    fn test_insert_text_selected_multi_line_replacing_selection() {
+        let (mut editor, mut content) = build("a = ❱XX❰ end");
+
+        editor.insert_text_selected("1\nb = 2", &mut content);
+        assert_markup(&editor, &content, "a = ❱1\nb = 2❰ end");
+    }
+
+    #[test]
+    fn test_editing_a_row_bumps_only_its_generation() {
+        let (mut editor, mut content) = build("row0\nrow1\nrow2\nrow3");
+        let generations: Vec<u64> = (0..4).map(|i| content.line_generation(i)).collect();
+
+        editor.set_cursor_pos_r_c(2, 4);
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('x'),
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(content.line_generation(0), generations[0]);
+        assert_eq!(content.line_generation(1), generations[1]);
+        assert_ne!(content.line_generation(2), generations[2]);
+        assert_eq!(content.line_generation(3), generations[3]);
+
+        let generation_after_insert = content.line_generation(2);
+        editor.undo(&mut content);
+        assert_ne!(content.line_generation(2), generation_after_insert);
+        assert_ne!(content.line_generation(2), generations[2]);
+    }
+
+    #[test]
+    fn test_inserting_a_line_shifts_the_generations() {
+        let (mut editor, mut content) = build("row0\nrow1\nrow2");
+        let generations: Vec<u64> = (0..3).map(|i| content.line_generation(i)).collect();
+
+        editor.set_cursor_pos_r_c(1, 0);
+        editor.handle_input_undoable(
+            EditorInputEvent::Enter,
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(content.line_count(), 4);
+        assert_eq!(content.line_generation(0), generations[0]);
+        assert!(!generations.contains(&content.line_generation(1)));
+        assert_eq!(content.line_generation(2), generations[1]);
+        assert_eq!(content.line_generation(3), generations[2]);
+
+        editor.set_cursor_pos_r_c(1, 0);
+        editor.handle_input_undoable(EditorInputEvent::Del, InputModifiers::none(), &mut content);
+        assert_eq!(content.line_count(), 3);
+        assert_eq!(content.line_generation(0), generations[0]);
+        assert_eq!(content.line_generation(1), generations[1]);
+        assert_eq!(content.line_generation(2), generations[2]);
+    }
+
+    #[test]
+    fn test_indent_level() {
+        let (_editor, content) =
+            build("no indent\n    four spaces\n\ttab\n\t\ttwo tabs\n  \tmixed\n   ");
+
+        assert_eq!(content.indent_level(0, 4), 0);
+        assert_eq!(content.indent_level(1, 4), 4);
+        assert_eq!(content.indent_level(2, 4), 4);
+        assert_eq!(content.indent_level(3, 4), 8);
+        assert_eq!(content.indent_level(3, 2), 4);
+        // the tab advances to the next tab stop after the two spaces
+        assert_eq!(content.indent_level(4, 4), 4);
+        assert_eq!(content.indent_level(4, 2), 4);
+        // whitespace only line
+        assert_eq!(content.indent_level(5, 4), 3);
+    }
+
+    #[test]
+    fn test_fold_range() {
+        let (_editor, content) =
+            build("header\n  child 1\n    grandchild\n\n  child 2\n\nsibling\n\tchild 3\nlast");
+
+        assert_eq!(content.fold_range(0, 4), Some((1, 4)));
+        assert_eq!(content.fold_range(1, 4), Some((2, 2)));
+        assert_eq!(content.fold_range(2, 4), None);
+        assert_eq!(content.fold_range(3, 4), None);
+        assert_eq!(content.fold_range(4, 4), None);
+        assert_eq!(content.fold_range(6, 4), Some((7, 7)));
+        assert_eq!(content.fold_range(8, 4), None);
+    }
+
+    #[test]
+    fn test_move_block_down_past_a_single_line() {
+        let (mut editor, mut content) = build("b1\nb█2\nb3\nx\ny");
+
+        assert_eq!(
+            editor.move_block_undoable(0..3, 1, &mut content),
+            Some(RowModificationType::AllLinesFrom(0))
+        );
+        assert_markup(&editor, &content, "x\nb1\nb█2\nb3\ny");
+
+        // clamped at the end of the document
+        editor.move_block_undoable(1..4, 10, &mut content);
+        assert_eq!(content.get_content(), "x\ny\nb1\nb2\nb3");
+        assert_eq!(editor.move_block_undoable(2..5, 1, &mut content), None);
+
+        editor.undo(&mut content);
+        assert_eq!(content.get_content(), "b1\nb2\nb3\nx\ny");
+    }
+
+    #[test]
+    fn test_move_block_up_past_another_block() {
+        let (mut editor, mut content) = build("a❱1\na2\nb1\nb2❰\nb3\nc");
+
+        editor.move_block_undoable(2..5, -2, &mut content);
+        assert_markup(&editor, &content, "b1\nb2❰\nb3\na❱1\na2\nc");
+        assert_eq!(editor.move_block_undoable(0..3, -1, &mut content), None);
+    }
+
+    #[test]
+    fn test_toggling_wrap_keeps_the_cursor() {
+        let (mut editor, content) = build("lorem ipsum dolor si█t amet\n\nshort");
+        assert_eq!(editor.display_row_count(&content), 3);
+
+        editor.set_wrap(Some(10));
+        assert_eq!(editor.wrap_width(), Some(10));
+        assert_eq!(
+            editor.get_selection().get_cursor_pos(),
+            Pos::from_row_column(0, 20)
+        );
+        // "lorem " "ipsum " "dolor sit " "amet", "", "short"
+        assert_eq!(editor.display_row_count(&content), 6);
+
+        editor.set_wrap(None);
+        assert_eq!(
+            editor.get_selection().get_cursor_pos(),
+            Pos::from_row_column(0, 20)
+        );
+        assert_eq!(editor.display_row_count(&content), 3);
+    }
+
+    #[test]
+    fn test_line_slice() {
+        let (_editor, content) = build("first\nprice = 12 $");
+
+        assert_eq!(content.line_slice(1, 8..10), "12");
+        assert_eq!(content.line_slice(0, 0..5), "first");
+        // clamped to the line length
+        assert_eq!(content.line_slice(0, 2..50), "rst");
+        assert_eq!(content.line_slice(0, 10..50), "");
+    }
+
+    #[test]
+    fn test_surround_selection_with_a_word_selected() {
+        let (mut editor, mut content) = build("some ❱important❰ text");
+
+        editor.surround_selection("**", "**", &mut content);
+        assert_markup(&editor, &content, "some **❱important❰** text");
+
+        editor.undo(&mut content);
+        assert_eq!(content.get_content(), "some important text");
+    }
+
+    #[test]
+    fn test_surround_multi_line_selection() {
+        let (mut editor, mut content) = build("fi❰rst\nsecond\nthi❱rd");
+
+        editor.surround_selection("```\n", "\n```", &mut content);
+        assert_markup(&editor, &content, "fi```\n❰rst\nsecond\nthi❱\n```rd");
+    }
+
+    #[test]
+    fn test_surround_without_selection() {
+        let (mut editor, mut content) = build("a█b");
+
+        editor.surround_selection("`", "`", &mut content);
+        assert_markup(&editor, &content, "a`█`b");
+    }
+
+    #[test]
+    fn test_unbalanced_brackets_with_extra_closing() {
+        let (_editor, content) = build("a = (1 + [2 * 3])\nb = a * 2)\nc = (a + b)");
+
+        assert_eq!(
+            content.unbalanced_brackets(),
+            vec![Pos::from_row_column(1, 9)]
+        );
+    }
+
+    #[test]
+    fn test_unbalanced_brackets_with_unclosed_opening() {
+        let (_editor, content) = build("a = (1 + (2\n* 3)\nb = [4 }");
+
+        assert_eq!(
+            content.unbalanced_brackets(),
+            vec![
+                Pos::from_row_column(0, 4),
+                Pos::from_row_column(2, 4),
+                Pos::from_row_column(2, 7),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_balanced_brackets() {
+        let (_editor, content) = build("sum(\n  [1, 2],\n  {3}\n)");
+
+        assert!(content.unbalanced_brackets().is_empty());
+    }
+
+    #[test]
+    fn test_tab_without_forced_soft_tabs() {
+        let (mut editor, mut content) = build("abc\n  def");
+        editor.set_force_soft_tabs(false);
+
+        // at column 0
+        editor.set_cursor_pos_r_c(0, 0);
+        editor.handle_input_undoable(EditorInputEvent::Tab, InputModifiers::none(), &mut content);
+        assert_markup(&editor, &content, "    █abc\n  def");
+
+        // inside the leading whitespace
+        editor.set_cursor_pos_r_c(1, 2);
+        editor.handle_input_undoable(EditorInputEvent::Tab, InputModifiers::none(), &mut content);
+        assert_eq!(content.get_content(), "    abc\n    def");
+
+        // mid-line
+        editor.set_cursor_pos_r_c(0, 5);
+        editor.handle_input_undoable(EditorInputEvent::Tab, InputModifiers::none(), &mut content);
+        assert_markup(&editor, &content, "    a\t█bc\n    def");
+    }
+
+    #[test]
+    fn test_tab_with_forced_soft_tabs_mid_line() {
+        let (mut editor, mut content) = build("a█bcdef");
+        editor.set_force_soft_tabs(true);
+
+        editor.handle_input_undoable(EditorInputEvent::Tab, InputModifiers::none(), &mut content);
+        assert_eq!(content.get_content(), "a   bcdef");
+    }
+
+    #[test]
+    fn test_selection_summary() {
+        let (mut editor, content) = build("first\nsecond\nthird");
+        assert_eq!(editor.selection_summary(&content), None);
+
+        editor.set_cursor_range(Pos::from_row_column(2, 2), Pos::from_row_column(0, 3));
+        assert_eq!(
+            editor.selection_summary(&content),
+            Some(SelectionSummary {
+                line_count: 3,
+                // "st\n" + "second\n" + "th"
+                char_count: 12,
+                is_multiline: true,
+            })
+        );
+
+        editor.set_cursor_range(Pos::from_row_column(1, 1), Pos::from_row_column(1, 4));
+        assert_eq!(
+            editor.selection_summary(&content),
+            Some(SelectionSummary {
+                line_count: 1,
+                char_count: 3,
+                is_multiline: false,
+            })
+        );
+    }
+
+    #[test]
+    fn test_clear_to_single_line() {
+        let (mut editor, mut content) = build("first\nsecond\nthir█d");
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('x'),
+            InputModifiers::none(),
+            &mut content,
+        );
+
+        editor.clear_to_single_line(&mut content);
+        assert_eq!(content.line_count(), 1);
+        assert_eq!(content.line_len(0), 0);
+        assert_eq!(content.get_content(), "");
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 0));
+        assert!(content.undo_stack.is_empty());
+
+        // the editor is usable after clearing
+        editor.handle_input_undoable(EditorInputEvent::Down, InputModifiers::none(), &mut content);
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('a'),
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(content.get_content(), "a");
+    }
+
+    #[test]
+    fn test_shift_tab_outdents_the_line() {
+        test_normal_undo_redo(TestParams2 {
+            initial_content: "first\n      sec█ond",
+            text_input: None,
+            inputs: &[EditorInputEvent::Tab],
+            delay_after_inputs: &[],
+            modifiers: InputModifiers::shift(),
+            expected_content: "first\n  sec█ond",
+        });
+        test(
+            "\tsec█ond",
+            &[EditorInputEvent::Tab],
+            InputModifiers::shift(),
+            "sec█ond",
+        );
+    }
+
+    #[test]
+    fn test_shift_tab_with_less_than_one_indent_unit() {
+        test(
+            "  a█bc",
+            &[EditorInputEvent::Tab],
+            InputModifiers::shift(),
+            "a█bc",
+        );
+        // the cursor does not go below zero
+        test(
+            " █ abc",
+            &[EditorInputEvent::Tab],
+            InputModifiers::shift(),
+            "█abc",
+        );
+        test(
+            "ab█c",
+            &[EditorInputEvent::Tab],
+            InputModifiers::shift(),
+            "ab█c",
+        );
+    }
+
+    #[test]
+    fn test_cursor_offset_round_trip() {
+        let (mut editor, content) = build("first\n\nsecond line\nthird");
+
+        for row in 0..content.line_count() {
+            for column in 0..=content.line_len(row) {
+                editor.set_cursor_pos_r_c(row, column);
+                let offset = editor.cursor_offset(&content);
+                editor.set_cursor_pos_r_c(0, 0);
+                editor.set_cursor_offset(offset, &content);
+                assert_eq!(editor.get_selection(), Selection::single_r_c(row, column));
+            }
+        }
+
+        editor.set_cursor_pos_r_c(2, 3);
+        assert_eq!(editor.cursor_offset(&content), 10);
+        editor.set_cursor_offset(1000, &content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(3, 5));
+    }
+
+    #[test]
+    fn test_max_paste_chars() {
+        let (mut editor, mut content) = build("");
+        editor.set_max_paste_chars(Some(100));
+
+        // 10000 chars
+        editor.paste(
+            &("áb\n".repeat(3333) + "x"),
+            OverflowPolicy::Wrap,
+            &mut content,
+        );
+        assert_eq!(editor.dropped_paste_chars(), 9900);
+        assert_eq!(content.get_content(), "áb\n".repeat(33) + "á");
+        assert_eq!(content.line_count(), 34);
+        assert_eq!(content.line_len(33), 1);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(33, 1));
+
+        editor.paste("short", OverflowPolicy::Wrap, &mut content);
+        assert_eq!(editor.dropped_paste_chars(), 0);
+
+        // only pastes are limited
+        let len_before = content.char_count();
+        editor.insert_text_undoable(&"x".repeat(200), &mut content);
+        assert_eq!(content.char_count(), len_before + 200 + 2);
+
+        editor.set_max_paste_chars(None);
+        editor.paste(&"x".repeat(200), OverflowPolicy::Wrap, &mut content);
+        assert_eq!(editor.dropped_paste_chars(), 0);
+    }
+
+    #[test]
+    fn test_diff_against_added_line() {
+        let (_editor, content) = build("a = 1\nb = 2\nc = a + b");
+
+        assert_eq!(content.diff_against("a = 1\nb = 2\nc = a + b"), vec![]);
+        assert_eq!(
+            content.diff_against("a = 1\nb = 2\nx = 3\nc = a + b"),
+            vec![RowDiff::Added(2)]
+        );
+    }
+
+    #[test]
+    fn test_diff_against_removed_line() {
+        let (_editor, content) = build("a = 1\nb = 2\nc = a + b");
+
+        assert_eq!(
+            content.diff_against("a = 1\nc = a + b"),
+            vec![RowDiff::Removed(1)]
+        );
+    }
+
+    #[test]
+    fn test_diff_against_modified_line() {
+        let (_editor, content) = build("a = 1\nb = 2\nc = a + b");
+
+        assert_eq!(
+            content.diff_against("a = 1\nb = 20\r\nc = a + b\nd = c"),
+            vec![
+                RowDiff::Changed {
+                    row: 1,
+                    other_row: 1
+                },
+                RowDiff::Added(3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_swap_rows() {
+        let (mut editor, mut content) = build("short\nmiddle\na much longer line");
+        *content.mut_data(0) = 10;
+        *content.mut_data(2) = 12;
+        editor.set_cursor_pos_r_c(2, 15);
+
+        assert_eq!(
+            editor.swap_rows(2, 0, &mut content),
+            Some(RowModificationType::AllLinesFrom(0))
+        );
+        assert_eq!(content.get_content(), "a much longer line\nmiddle\nshort");
+        assert_eq!(content.line_len(0), 18);
+        assert_eq!(content.line_len(2), 5);
+        assert_eq!(*content.get_data(0), 12);
+        assert_eq!(*content.get_data(2), 10);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 15));
+
+        editor.undo(&mut content);
+        assert_markup(&editor, &content, "short\nmiddle\na much longer l█ine");
+
+        assert_eq!(editor.swap_rows(1, 3, &mut content), None);
+        assert_eq!(editor.swap_rows(1, 1, &mut content), None);
+    }
+
+    #[test]
+    fn test_apply_edit_before_the_selection() {
+        let (mut editor, mut content) = build("abc\nsome text");
+        // "text" is selected
+        editor.set_cursor_range(Pos::from_row_column(1, 5), Pos::from_row_column(1, 9));
+
+        editor.apply_edit(1, 0, "XY\nZ", &mut content);
+        assert_markup(&editor, &content, "aXY\nZbc\nsome ❱text❰");
+
+        // removal before the selection
+        editor.apply_edit(0, 5, "", &mut content);
+        assert_markup(&editor, &content, "bc\nsome ❱text❰");
+    }
+
+    #[test]
+    fn test_apply_edit_inside_the_selection() {
+        let (mut editor, mut content) = build("01❰2345❱6789");
+
+        editor.apply_edit(4, 0, "abc", &mut content);
+        assert_markup(&editor, &content, "01❰23abc45❱6789");
+
+        // the removed range contains the start of the selection
+        editor.apply_edit(1, 3, "", &mut content);
+        assert_markup(&editor, &content, "0❰abc45❱6789");
+    }
+
+    #[test]
+    fn test_apply_edit_after_the_selection() {
+        let (mut editor, mut content) = build("01❱23❰456789");
+
+        editor.apply_edit(4, 2, "x\ny", &mut content);
+        assert_markup(&editor, &content, "01❱23❰x\ny6789");
+
+        // a collapsed cursor at the position of the edit is shifted
+        editor.set_cursor_pos_r_c(1, 1);
+        editor.apply_edit(7, 0, "zz", &mut content);
+        assert_markup(&editor, &content, "0123x\nyzz█6789");
+    }
+
+    #[test]
+    fn test_number_literals() {
+        let (_editor, content) =
+            build("((0b00101 AND 0xFF00) XOR 16) << 2\nx1 = 3.25 + 12abc + 0x");
+
+        let literals = content.number_literals();
+        assert_eq!(
+            literals,
+            vec![
+                (
+                    Selection::range(Pos::from_row_column(0, 2), Pos::from_row_column(0, 9)),
+                    5.0
+                ),
+                (
+                    Selection::range(Pos::from_row_column(0, 14), Pos::from_row_column(0, 20)),
+                    65280.0
+                ),
+                (
+                    Selection::range(Pos::from_row_column(0, 26), Pos::from_row_column(0, 28)),
+                    16.0
+                ),
+                (
+                    Selection::range(Pos::from_row_column(0, 33), Pos::from_row_column(0, 34)),
+                    2.0
+                ),
+                (
+                    Selection::range(Pos::from_row_column(1, 5), Pos::from_row_column(1, 9)),
+                    3.25
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tsv_round_trip() {
+        let (mut editor, mut content) = build("values:\t1\t22\t333 ;\nvalues:\t4\t55\t666 ;\n\nx");
+        assert_eq!(editor.selection_as_tsv(&content), None);
+
+        editor.set_cursor_range(Pos::from_row_column(1, 16), Pos::from_row_column(0, 8));
+        let tsv = editor.selection_as_tsv(&content).unwrap();
+        assert_eq!(tsv, "1\t22\t333\n4\t55\t666");
+
+        editor.set_cursor_pos_r_c(2, 2);
+        editor.paste_tsv(&tsv, &mut content);
+        assert_eq!(
+            content.get_content(),
+            "values:\t1\t22\t333 ;\nvalues:\t4\t55\t666 ;\n  1\t22\t333\nx 4\t55\t666"
+        );
+
+        editor.set_cursor_range(Pos::from_row_column(2, 2), Pos::from_row_column(3, 10));
+        assert_eq!(editor.selection_as_tsv(&content).unwrap(), tsv);
+
+        // rows are appended if the block does not fit
+        editor.set_cursor_pos_r_c(3, 0);
+        editor.paste_tsv("a\tb\nc\td", &mut content);
+        assert_eq!(content.line_count(), 5);
+        assert_eq!(content.get_content().lines().nth(4).unwrap(), "c\td");
+    }
+
+    #[test]
+    fn test_tsv_cells_containing_spaces() {
+        let (mut editor, mut content) = build("");
+        editor.paste_tsv("12 km\t3 h\n4 mm\t56 s", &mut content);
+        assert_eq!(content.get_content(), "12 km\t3 h\n4 mm\t56 s");
+
+        editor.set_cursor_range(Pos::from_row_column(0, 0), Pos::from_row_column(1, 9));
+        assert_eq!(
+            editor.selection_as_tsv(&content).unwrap(),
+            "12 km\t3 h\n4 mm\t56 s"
+        );
+    }
+
+    #[test]
+    fn test_backspace_join_keeps_the_indentation_by_default() {
+        test(
+            "sum(1,\n█    2)",
+            &[EditorInputEvent::Backspace],
+            InputModifiers::none(),
+            "sum(1,█    2)",
+        );
+    }
+
+    #[test]
+    fn test_backspace_join_strips_the_indentation() {
+        let (mut editor, mut content) = build("sum(1,\n█    2)");
+        editor.set_strip_indent_on_join(true);
+
+        editor.handle_input_undoable(
+            EditorInputEvent::Backspace,
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_markup(&editor, &content, "sum(1,█2)");
+
+        editor.undo(&mut content);
+        assert_markup(&editor, &content, "sum(1,\n█    2)");
+    }
+
+    #[test]
+    fn test_indexed_lines() {
+        let (_editor, content) = build("first\n\nthird");
+
+        let lines: Vec<(usize, String)> = content
+            .indexed_lines()
+            .map(|(i, line)| (i, line.iter().collect()))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (0, "first".to_owned()),
+                (1, "".to_owned()),
+                (2, "third".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_default_auto_pairs() {
+        let (editor, _content) = build("");
+        assert_eq!(editor.auto_pair_closing_for('('), Some(')'));
+        assert_eq!(editor.auto_pair_closing_for('['), Some(']'));
+        assert_eq!(editor.auto_pair_closing_for('{'), Some('}'));
+        assert_eq!(editor.auto_pair_closing_for('"'), Some('"'));
+        assert_eq!(editor.auto_pair_closing_for('a'), None);
+        assert!(editor.is_auto_pair_closing(')'));
+        assert!(!editor.is_auto_pair_closing('"'));
+    }
+
+    #[test]
+    fn test_custom_auto_pairs() {
+        let (mut editor, _content) = build("");
+        editor.set_auto_pairs(&[('(', ')'), ('«', '»')]);
+        assert_eq!(editor.auto_pair_closing_for('('), Some(')'));
+        assert_eq!(editor.auto_pair_closing_for('«'), Some('»'));
+        assert!(editor.is_auto_pair_closing('»'));
+        // removed defaults
+        assert_eq!(editor.auto_pair_closing_for('['), None);
+        assert_eq!(editor.auto_pair_closing_for('"'), None);
+        assert!(!editor.is_auto_pair_closing(']'));
+    }
+
+    #[test]
+    fn test_remaining_line_capacity() {
+        let mut content = EditorContent::<usize>::new(10);
+        let mut editor = Editor::new(&mut content);
+        content.init_with("abcdefg\n");
+        assert_eq!(content.max_line_len(), 10);
+        assert_eq!(content.remaining_line_capacity(0), 3);
+        assert_eq!(content.remaining_line_capacity(1), 10);
+
+        editor.set_cursor_pos_r_c(0, 7);
+        for ch in "xyz".chars() {
+            editor.handle_input_undoable(
+                EditorInputEvent::Char(ch),
+                InputModifiers::none(),
+                &mut content,
+            );
+        }
+        assert_eq!(content.remaining_line_capacity(0), 0);
+    }
+
+    #[test]
+    fn test_word_mode_drag_across_words() {
+        let (mut editor, content) = build("alpha beta gamma delta");
+
+        editor.handle_click(7, 0, &content);
+        editor.handle_drag_with_mode(8, 0, DragMode::Word, &content);
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(0, 6), Pos::from_row_column(0, 10))
+        );
+
+        editor.handle_drag_with_mode(13, 0, DragMode::Word, &content);
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(0, 6), Pos::from_row_column(0, 16))
+        );
+
+        editor.handle_drag_with_mode(19, 0, DragMode::Word, &content);
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(0, 6), Pos::from_row_column(0, 22))
+        );
+    }
+
+    #[test]
+    fn test_word_mode_drag_backwards_keeps_the_anchor_word() {
+        let (mut editor, content) = build("alpha beta gamma delta");
+
+        editor.handle_click(13, 0, &content);
+        editor.handle_drag_with_mode(2, 0, DragMode::Word, &content);
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(0, 16), Pos::from_row_column(0, 0))
+        );
+    }
+
+    #[test]
+    fn test_line_mode_drag() {
+        let (mut editor, content) = build("first\nsecond\nthird");
+
+        editor.handle_click(3, 1, &content);
+        editor.handle_drag_with_mode(1, 2, DragMode::Line, &content);
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(1, 0), Pos::from_row_column(2, 5))
+        );
+        editor.handle_drag_with_mode(1, 0, DragMode::Line, &content);
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(1, 6), Pos::from_row_column(0, 0))
+        );
+    }
+
+    #[test]
+    fn test_normalized_selection() {
+        let (mut editor, _content) = build("first\nsecond");
+
+        let forward = Selection::range(Pos::from_row_column(0, 2), Pos::from_row_column(1, 3));
+        editor.set_selection_save_col(forward);
+        assert_eq!(editor.normalized_selection(), forward);
+
+        let backward = Selection::range(Pos::from_row_column(1, 3), Pos::from_row_column(0, 2));
+        editor.set_selection_save_col(backward);
+        let normalized = editor.normalized_selection();
+        assert_eq!(normalized, forward);
+
+        editor.set_selection_save_col(normalized);
+        assert_eq!(
+            editor.get_selection().get_range_ordered(),
+            backward.get_range_ordered()
+        );
+    }
+
+    #[test]
+    fn test_normalized_selection_collapsed() {
+        let (editor, _content) = build("fir█st");
+        assert_eq!(editor.normalized_selection(), Selection::single_r_c(0, 3));
+    }
+
+    #[test]
+    fn test_overtype_paste() {
+        let (mut editor, mut content) = build("abcdef");
+        editor.set_insert_mode(false);
+        editor.set_cursor_pos_r_c(0, 1);
+
+        editor.insert_text_undoable("XYZ", &mut content);
+        assert_eq!(content.get_content(), "aXYZef");
+        assert_eq!(content.line_len(0), 6);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 4));
+
+        editor.undo(&mut content);
+        assert_eq!(content.get_content(), "abcdef");
+    }
+
+    #[test]
+    fn test_overtype_paste_appends_past_the_end_of_line() {
+        let (mut editor, mut content) = build("abcdef");
+        editor.set_insert_mode(false);
+        editor.set_cursor_pos_r_c(0, 4);
+
+        editor.insert_text_undoable("XYZ", &mut content);
+        assert_markup(&editor, &content, "abcdXYZ█");
+    }
+
+    #[test]
+    fn test_overtype_char() {
+        let (mut editor, mut content) = build("abc");
+        editor.set_insert_mode(false);
+        editor.set_cursor_pos_r_c(0, 2);
+
+        for ch in "XY".chars() {
+            editor.handle_input_undoable(
+                EditorInputEvent::Char(ch),
+                InputModifiers::none(),
+                &mut content,
+            );
+        }
+        assert_markup(&editor, &content, "abXY█");
+    }
+
+    #[test]
+    fn test_display_index_to_pos() {
+        let (_editor, mut content) = build("");
+        // with width 6: "abc " | "defgh" | "" | "xy"
+        content.init_with("abc defgh\n\nxy");
+
+        assert_eq!(
+            content.display_index_to_pos(0, 6),
+            Pos::from_row_column(0, 0)
+        );
+        assert_eq!(
+            content.display_index_to_pos(2, 6),
+            Pos::from_row_column(0, 2)
+        );
+        assert_eq!(
+            content.display_index_to_pos(6, 6),
+            Pos::from_row_column(0, 4)
+        );
+        assert_eq!(
+            content.display_index_to_pos(8, 6),
+            Pos::from_row_column(0, 6)
+        );
+        assert_eq!(
+            content.display_index_to_pos(11, 6),
+            Pos::from_row_column(0, 9)
+        );
+        assert_eq!(
+            content.display_index_to_pos(14, 6),
+            Pos::from_row_column(1, 0)
+        );
+        assert_eq!(
+            content.display_index_to_pos(19, 6),
+            Pos::from_row_column(2, 1)
+        );
+        assert_eq!(
+            content.display_index_to_pos(100, 6),
+            Pos::from_row_column(2, 2)
+        );
+    }
+
+    #[test]
+    fn test_remove_char_on_empty_line() {
+        let (_editor, mut content) = build("abc\n\ndef");
+
+        assert!(!content.remove_char(1, 0));
+        assert_eq!(content.line_len(1), 0);
+        assert!(!content.remove_char(0, 3));
+        assert_eq!(content.get_content(), "abc\n\ndef");
+
+        assert!(content.remove_char(0, 2));
+        assert_eq!(content.get_content(), "ab\n\ndef");
+    }
+
+    #[test]
+    fn test_map_selected_lines_uppercase() {
+        let (mut editor, mut content) = build("before\nfi❱rst\nsecond\nt❰hird\nafter");
+
+        let modif = editor.map_selected_lines(|line| line.to_uppercase(), &mut content);
+        assert!(modif.is_some());
+        assert_markup(&editor, &content, "before\n❱FIRST\nSECOND\nTHIRD❰\nafter");
+
+        editor.undo(&mut content);
+        assert_eq!(content.get_content(), "before\nfirst\nsecond\nthird\nafter");
+    }
+
+    #[test]
+    fn test_map_selected_lines_rejects_line_breaks() {
+        let (mut editor, mut content) = build("f█irst\nsecond");
+
+        let modif = editor.map_selected_lines(|line| format!("{}\n", line), &mut content);
+        assert!(modif.is_none());
+        assert_markup(&editor, &content, "f█irst\nsecond");
+    }
+
+    #[test]
+    fn test_home_end_with_forward_selection() {
+        let (mut editor, mut content) = build("first\nsecond line");
+
+        let selection = Selection::range(Pos::from_row_column(0, 2), Pos::from_row_column(1, 3));
+        editor.set_selection_save_col(selection);
+        editor.handle_input_undoable(EditorInputEvent::Home, InputModifiers::none(), &mut content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(1, 0));
+
+        editor.set_selection_save_col(selection);
+        editor.handle_input_undoable(EditorInputEvent::End, InputModifiers::none(), &mut content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(1, 11));
+    }
+
+    #[test]
+    fn test_home_end_with_backward_selection() {
+        let (mut editor, mut content) = build("first\nsecond line");
+
+        let selection = Selection::range(Pos::from_row_column(1, 3), Pos::from_row_column(0, 2));
+        editor.set_selection_save_col(selection);
+        editor.handle_input_undoable(EditorInputEvent::Home, InputModifiers::none(), &mut content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 0));
+
+        editor.set_selection_save_col(selection);
+        editor.handle_input_undoable(EditorInputEvent::End, InputModifiers::none(), &mut content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 5));
+    }
+
+    #[test]
+    fn test_max_column_without_virtual_space() {
+        let (mut editor, mut content) = build("abc\nlonger line");
+        assert_eq!(editor.max_column(0, &content), 3);
+
+        editor.handle_click(10, 0, &content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 3));
+        editor.handle_input_undoable(EditorInputEvent::End, InputModifiers::none(), &mut content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 3));
+    }
+
+    #[test]
+    fn test_max_column_with_virtual_space() {
+        let (mut editor, mut content) = build("abc\nlonger line");
+        editor.set_virtual_space(Some(8));
+        assert_eq!(editor.max_column(0, &content), 8);
+        assert_eq!(editor.max_column(1, &content), 11);
+
+        editor.handle_click(20, 0, &content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 8));
+        editor.set_cursor_pos_r_c(0, 0);
+        editor.handle_input_undoable(EditorInputEvent::End, InputModifiers::none(), &mut content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 8));
+
+        editor.handle_click(5, 0, &content);
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('x'),
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_markup(&editor, &content, "abc  x█\nlonger line");
+
+        editor.undo(&mut content);
+        assert_eq!(content.get_content(), "abc\nlonger line");
+    }
+
+    #[test]
+    fn test_deleting_in_virtual_space_clamps_the_cursor() {
+        let (mut editor, mut content) = build("abc\ndef");
+        editor.set_virtual_space(Some(8));
+
+        editor.handle_click(6, 0, &content);
+        editor.handle_input_undoable(EditorInputEvent::Del, InputModifiers::none(), &mut content);
+        assert_markup(&editor, &content, "abc█\ndef");
+
+        editor.handle_click(6, 0, &content);
+        editor.handle_input_undoable(
+            EditorInputEvent::Backspace,
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_markup(&editor, &content, "abc█\ndef");
+
+        // at the end of the line it deletes again
+        editor.handle_input_undoable(EditorInputEvent::Del, InputModifiers::none(), &mut content);
+        assert_markup(&editor, &content, "abc█def");
+    }
+
+    #[test]
+    fn test_wide_char_aware_click_in_virtual_space() {
+        let (mut editor, content) = build("日本\nx");
+        editor.set_wide_char_aware_click(true);
+        editor.set_virtual_space(Some(8));
+
+        editor.handle_click(3, 0, &content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 2));
+        editor.handle_click(6, 0, &content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 4));
+        editor.handle_click(20, 0, &content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 8));
+
+        editor.set_virtual_space(None);
+        editor.handle_click(20, 0, &content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 2));
+    }
+
+    #[test]
+    fn test_degenerate_selections_are_not_ranges() {
+        let (mut editor, content) = build("abcdef");
+
+        let pos = Pos::from_row_column(0, 2);
+        let degenerate = Selection {
+            start: pos,
+            end: Some(pos),
+        };
+        assert!(!degenerate.is_range());
+        assert_eq!(degenerate.is_range_ordered(), None);
+        assert_eq!(degenerate.normalized(), Selection::single(pos));
+        assert_eq!(
+            Editor::get_selected_text_single_line(degenerate, &content),
+            None
+        );
+        assert!(!Selection::range(pos, pos).is_range());
+        assert!(!Selection::single(pos).extend(pos).is_range());
+
+        editor.set_selection_save_col(degenerate);
+        assert_eq!(editor.get_selection(), Selection::single(pos));
+        assert_eq!(editor.get_selection().end, None);
+    }
+
+    #[test]
+    fn test_word_boundaries_match_ctrl_left_right() {
+        let (mut editor, mut content) = build("12 + some_var * (3 \"str\")");
+        let line_len = content.line_len(0);
+
+        for col in 1..=line_len {
+            editor.set_cursor_pos_r_c(0, col);
+            editor.handle_input_undoable(
+                EditorInputEvent::Left,
+                InputModifiers::ctrl(),
+                &mut content,
+            );
+            assert_eq!(
+                content.prev_word_boundary(Pos::from_row_column(0, col)),
+                editor.get_selection().get_cursor_pos().column,
+                "col: {}",
+                col
+            );
+        }
+        for col in 0..line_len {
+            editor.set_cursor_pos_r_c(0, col);
+            editor.handle_input_undoable(
+                EditorInputEvent::Right,
+                InputModifiers::ctrl(),
+                &mut content,
+            );
+            assert_eq!(
+                content.next_word_boundary(Pos::from_row_column(0, col)),
+                editor.get_selection().get_cursor_pos().column,
+                "col: {}",
+                col
+            );
+        }
+        assert_eq!(content.prev_word_boundary(Pos::from_row_column(0, 13)), 5);
+        assert_eq!(content.next_word_boundary(Pos::from_row_column(0, 5)), 13);
+    }
+
+    #[test]
+    fn test_ctrl_l_selects_the_line_to_be_replaced() {
+        let (mut editor, mut content) = build("first\nsec█ond\nthird");
+
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('l'),
+            InputModifiers::ctrl(),
+            &mut content,
+        );
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(1, 0), Pos::from_row_column(1, 6))
+        );
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('x'),
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_markup(&editor, &content, "first\nx█\nthird");
+    }
+
+    #[test]
+    fn test_can_insert() {
+        let mut content = EditorContent::<usize>::new(10);
+        let _editor = Editor::new(&mut content);
+        content.init_with("abcdefg");
+        assert!(content.can_insert(0, 2));
+        assert!(content.can_insert(0, 3));
+        assert!(!content.can_insert(0, 4));
+    }
+
+    #[test]
+    fn test_can_merge() {
+        let mut content = EditorContent::<usize>::new(10);
+        let _editor = Editor::new(&mut content);
+        content.init_with("abcdefg\nxyz\nxyzw");
+        // 7 + 3 is exactly the limit
+        assert!(content.can_merge(0));
+        assert!(content.can_merge(1));
+        content.init_with("abcdefg\nxyzw");
+        assert!(!content.can_merge(0));
+        // there is no next row
+        assert!(!content.can_merge(1));
+    }
+
+    #[test]
+    fn test_get_block_text() {
+        let (mut editor, content) = build("abcdefgh\nijklmn❰op\nqrstuvwx\nyz❱012345");
+
+        assert_eq!(
+            editor.get_block_text(&content),
+            Some("klmn\nstuv\n0123".to_owned())
+        );
+
+        editor.set_cursor_pos_r_c(1, 2);
+        assert_eq!(editor.get_block_text(&content), None);
+    }
+
+    #[test]
+    fn test_get_block_text_with_short_lines() {
+        let (editor, content) = build("a❱bcdefgh\nij\nqrstu❰vwx");
+
+        assert_eq!(
+            editor.get_block_text(&content),
+            Some("bcde\nj\nrstu".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_select_all_in_empty_document() {
+        let (mut editor, mut content) = build("");
+
+        editor.select_all(&content);
+        assert!(!editor.get_selection().is_range());
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 0));
+        assert_eq!(editor.get_selected_text(&content), None);
+
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('a'),
+            InputModifiers::ctrl(),
+            &mut content,
+        );
+        assert!(!editor.get_selection().is_range());
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('c'),
+            InputModifiers::ctrl(),
+            &mut content,
+        );
+        assert_eq!(editor.clipboard, "");
+    }
+
+    #[test]
+    fn test_select_all_and_get_selected_text() {
+        let (mut editor, content) = build("first\nsecond");
+
+        editor.select_all(&content);
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(0, 0), Pos::from_row_column(1, 6))
+        );
+        assert_eq!(
+            editor.get_selected_text(&content),
+            Some("first\nsecond".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_prefix_and_unprefix_all_lines() {
+        let (mut editor, mut content) = build("fi❱rst\n\nsec❰ond");
+
+        editor.prefix_all_lines("> ", true, &mut content);
+        assert_markup(&editor, &content, "> fi❱rst\n\n> sec❰ond");
+
+        editor.set_cursor_pos_r_c(2, 1);
+        editor.unprefix_all_lines("> ", &mut content);
+        assert_markup(&editor, &content, "first\n\n█second");
+
+        editor.prefix_all_lines("> ", false, &mut content);
+        assert_eq!(content.get_content(), "> first\n> \n> second");
+    }
+
+    #[test]
+    fn test_prefix_all_lines_respects_max_line_len() {
+        let mut content = EditorContent::<usize>::new(7);
+        let mut editor = Editor::new(&mut content);
+        content.init_with("first\nsecond");
+        editor.set_cursor_pos_r_c(1, 3);
+
+        assert!(editor.prefix_all_lines("> ", true, &mut content).is_none());
+        assert_markup(&editor, &content, "first\nsec█ond");
+    }
+
+    #[test]
+    fn test_selection_covering() {
+        let (editor, _content) = build("");
+
+        let positions = [
+            Pos::from_row_column(1, 2),
+            Pos::from_row_column(0, 7),
+            Pos::from_row_column(1, 0),
+        ];
+        assert_eq!(
+            editor.selection_covering(&positions),
+            Some(Selection::range(
+                Pos::from_row_column(0, 7),
+                Pos::from_row_column(1, 2)
+            ))
+        );
+        assert_eq!(
+            editor.selection_covering(&[Pos::from_row_column(0, 3)]),
+            Some(Selection::single_r_c(0, 3))
+        );
+        assert_eq!(editor.selection_covering(&[]), None);
+    }
+
+    #[test]
+    fn test_enter_at_max_line_count_is_rejected() {
+        let (mut editor, mut content) = build("first\nsecond\nthird");
+        editor.set_max_line_count(Some(3));
+        editor.set_cursor_pos_r_c(2, 2);
+
+        let modif = editor.handle_input_undoable(
+            EditorInputEvent::Enter,
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(modif, None);
+        assert_eq!(editor.last_input_effect(), InputEffect::Rejected);
+        assert_markup(&editor, &content, "first\nsecond\nth█ird");
+
+        // replacing a multiline selection does not add a line
+        editor.set_selection_save_col(Selection::range(
+            Pos::from_row_column(1, 3),
+            Pos::from_row_column(2, 2),
+        ));
+        let modif = editor.handle_input_undoable(
+            EditorInputEvent::Enter,
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert!(modif.is_some());
+        assert_eq!(content.get_content(), "first\nsec\nird");
+    }
+
+    #[test]
+    fn test_word_ranges_in_line() {
+        let (_editor, content) = build("price = 12 * unit_cost");
+        assert_eq!(content.word_ranges_in_line(0), vec![0..5, 8..10, 13..22]);
+    }
+
+    #[test]
+    fn test_word_frequencies() {
+        let (_editor, content) = build("Apple + pear\napple * 2\n\npear + apple");
+
+        let frequencies = content.word_frequencies(false);
+        assert_eq!(frequencies.get("apple"), Some(&2));
+        assert_eq!(frequencies.get("Apple"), Some(&1));
+        assert_eq!(frequencies.get("pear"), Some(&2));
+        assert_eq!(frequencies.get("2"), Some(&1));
+        assert_eq!(frequencies.len(), 4);
+
+        let frequencies = content.word_frequencies(true);
+        assert_eq!(frequencies.get("apple"), Some(&3));
+        assert_eq!(frequencies.get("Apple"), None);
+        assert_eq!(frequencies.len(), 3);
+    }
+
+    #[test]
+    fn test_find_char_in_line_forward() {
+        let (mut editor, content) = build("a,bc,def,g\nx,y");
+
+        assert!(editor.find_char_in_line(',', true, false, false, &content));
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 1));
+        assert!(editor.find_char_in_line(',', true, false, false, &content));
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 4));
+        assert!(editor.find_char_in_line(',', true, true, false, &content));
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 7));
+
+        assert!(editor.find_char_in_line('g', true, false, true, &content));
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(0, 7), Pos::from_row_column(0, 9))
+        );
+
+        // the next line is not searched
+        assert!(!editor.find_char_in_line('x', true, false, false, &content));
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(0, 7), Pos::from_row_column(0, 9))
+        );
+    }
+
+    #[test]
+    fn test_find_char_in_line_backward() {
+        let (mut editor, content) = build("a,bc,def,g█");
+
+        assert!(editor.find_char_in_line(',', false, false, false, &content));
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 8));
+        assert!(editor.find_char_in_line(',', false, true, false, &content));
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 5));
+        assert!(editor.find_char_in_line('a', false, false, true, &content));
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(0, 5), Pos::from_row_column(0, 0))
+        );
+        assert!(!editor.find_char_in_line('z', false, false, false, &content));
+    }
+
+    #[test]
+    fn test_view_state_restore_is_clamped() {
+        let (mut editor, mut content) = build("first\nsecond li█ne\nthird line");
+        editor.handle_input_undoable(EditorInputEvent::Up, InputModifiers::none(), &mut content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 5));
+        let state = editor.view_state();
+
+        editor.set_selection_save_col(Selection::range(
+            Pos::from_row_column(1, 2),
+            Pos::from_row_column(2, 10),
+        ));
+        editor.set_mark();
+        editor.set_scroll_top(2);
+        let other_pane_state = editor.view_state();
+        assert_eq!(
+            other_pane_state,
+            ViewState {
+                selection: Selection::range(
+                    Pos::from_row_column(1, 2),
+                    Pos::from_row_column(2, 10)
+                ),
+                last_column_index: 10,
+                mark: Some(Pos::from_row_column(2, 10)),
+                scroll_top: 2,
+            }
+        );
+
+        // edit the content in the other pane
+        content.init_with("fi\nsecond");
+        editor.restore_view_state(state, &content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 2));
+        assert_eq!(editor.mark(), None);
+        assert_eq!(editor.scroll_top(), 0);
+        // the remembered column is kept
+        editor.handle_input_undoable(EditorInputEvent::Down, InputModifiers::none(), &mut content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(1, 6));
+
+        editor.restore_view_state(other_pane_state, &content);
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(1, 2), Pos::from_row_column(1, 6))
+        );
+        assert_eq!(editor.mark(), Some(Pos::from_row_column(1, 6)));
+        // the content has only two rows
+        assert_eq!(editor.scroll_top(), 1);
+    }
+
+    #[test]
+    fn test_word_jumps_over_tabs() {
+        let (mut editor, mut content) = build("ab\tcd\t\tef");
+
+        editor.set_cursor_pos_r_c(0, 0);
+        let mut right_stops = vec![];
+        for _ in 0..4 {
+            editor.handle_input_undoable(
+                EditorInputEvent::Right,
+                InputModifiers::ctrl(),
+                &mut content,
+            );
+            right_stops.push(editor.get_selection().get_cursor_pos().column);
+        }
+        assert_eq!(right_stops, vec![2, 5, 9, 9]);
+
+        let mut left_stops = vec![];
+        for _ in 0..4 {
+            editor.handle_input_undoable(
+                EditorInputEvent::Left,
+                InputModifiers::ctrl(),
+                &mut content,
+            );
+            left_stops.push(editor.get_selection().get_cursor_pos().column);
+        }
+        assert_eq!(left_stops, vec![7, 3, 0, 0]);
+    }
+
+    #[test]
+    fn test_ctrl_backspace_and_del_over_tabs() {
+        let (mut editor, mut content) = build("ab\tcd\t\tef");
+
+        editor.set_cursor_pos_r_c(0, 7);
+        editor.handle_input_undoable(
+            EditorInputEvent::Backspace,
+            InputModifiers::ctrl(),
+            &mut content,
+        );
+        assert_markup(&editor, &content, "ab\t█ef");
+
+        editor.set_cursor_pos_r_c(0, 0);
+        editor.handle_input_undoable(EditorInputEvent::Del, InputModifiers::ctrl(), &mut content);
+        assert_eq!(content.get_content(), "\tef");
+        editor.handle_input_undoable(EditorInputEvent::Del, InputModifiers::ctrl(), &mut content);
+        assert_markup(&editor, &content, "█ef");
+    }
+
+    #[test]
+    fn test_same_visual_row() {
+        let (_editor, mut content) = build("");
+        // with width 6: "abc " | "defgh " | "ij"
+        content.init_with("abc defgh ij\nabc");
+
+        let pos = |row, col| Pos::from_row_column(row, col);
+        assert!(content.same_visual_row(pos(0, 0), pos(0, 3), 6));
+        assert!(content.same_visual_row(pos(0, 4), pos(0, 9), 6));
+        assert!(content.same_visual_row(pos(0, 10), pos(0, 12), 6));
+        assert!(!content.same_visual_row(pos(0, 3), pos(0, 4), 6));
+        assert!(!content.same_visual_row(pos(0, 1), pos(0, 11), 6));
+        assert!(!content.same_visual_row(pos(0, 1), pos(1, 1), 6));
+        // without wrapping
+        assert!(content.same_visual_row(pos(0, 1), pos(0, 11), 80));
+    }
+
+    #[test]
+    fn test_duplicate_selection() {
+        let (mut editor, mut content) = build("12 ❱+ 34❰ * 5");
+
+        assert!(editor.duplicate_selection_undoable(&mut content).is_some());
+        assert_markup(&editor, &content, "12 + 34❱+ 34❰ * 5");
+
+        editor.undo(&mut content);
+        assert_eq!(content.get_content(), "12 + 34 * 5");
+    }
+
+    #[test]
+    fn test_duplicate_selection_single_char() {
+        let (mut editor, mut content) = build("1█2 + 34");
+
+        assert!(editor.duplicate_selection_undoable(&mut content).is_some());
+        assert_markup(&editor, &content, "11█2 + 34");
+
+        editor.set_cursor_pos_r_c(0, 0);
+        assert!(editor.duplicate_selection_undoable(&mut content).is_none());
+    }
+
+    #[test]
+    fn test_duplicate_selection_overflow() {
+        let mut content = EditorContent::<usize>::new(8);
+        let mut editor = Editor::new(&mut content);
+        content.init_with("abcdef");
+        editor.set_selection_save_col(Selection::range(
+            Pos::from_row_column(0, 0),
+            Pos::from_row_column(0, 3),
+        ));
+
+        assert!(editor.duplicate_selection_undoable(&mut content).is_none());
+        assert_markup(&editor, &content, "❱abc❰def");
+    }
+
+    #[test]
+    fn test_byte_len() {
+        let (_editor, mut content) = build("");
+        assert_eq!(content.byte_len(), 0);
+
+        content.init_with("árvíztűrő\n\n12 € → 💰");
+        assert_eq!(content.byte_len(), content.get_content().len());
+        assert_eq!(content.byte_len(), 30);
+    }
+
+    #[test]
+    fn test_push_char() {
+        let mut content = EditorContent::<usize>::new(10);
+        let mut editor = Editor::new(&mut content);
+        content.init_with("log:");
+
+        for ch in "0123456789abc\nend".chars() {
+            editor.push_char(ch, &mut content);
+        }
+        assert_markup(&editor, &content, "log:012345\n6789abc\nend█");
+        assert!(content.undo_stack.is_empty());
+    }
+
+    #[test]
+    fn test_detected_line_ending() {
+        let (_editor, mut content) = build("");
+        assert_eq!(content.detected_line_ending(), LineEnding::Lf);
+
+        content.init_with("first\r\nsecond\r\nthird");
+        assert_eq!(content.detected_line_ending(), LineEnding::CrLf);
+        assert_eq!(content.get_content(), "first\nsecond\nthird");
+        assert_eq!(
+            content.get_content_with(content.detected_line_ending()),
+            "first\r\nsecond\r\nthird"
+        );
+
+        content.init_with("first\nsecond\nthird");
+        assert_eq!(content.detected_line_ending(), LineEnding::Lf);
+        assert_eq!(
+            content.get_content_with(content.detected_line_ending()),
+            "first\nsecond\nthird"
+        );
+
+        content.init_with("first\r\nsecond\nthird\r\nfourth");
+        assert_eq!(content.detected_line_ending(), LineEnding::CrLf);
+        content.init_with("first\r\nsecond\nthird\nfourth");
+        assert_eq!(content.detected_line_ending(), LineEnding::Lf);
+    }
+
+    #[test]
+    fn test_move_to_word_start_and_end() {
+        let (mut editor, content) = build("price = unit_cost * 12");
+
+        editor.set_cursor_pos_r_c(0, 11);
+        editor.move_to_word_start(&content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 8));
+        // already at the start
+        editor.move_to_word_start(&content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 8));
+
+        editor.move_to_word_end(&content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 17));
+        editor.move_to_word_end(&content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 17));
+
+        editor.set_selection_save_col(Selection::range(
+            Pos::from_row_column(0, 0),
+            Pos::from_row_column(0, 2),
+        ));
+        editor.move_to_word_end(&content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 5));
+    }
+
+    #[test]
+    fn test_typing_with_degenerate_selection() {
+        let degenerate = Selection {
+            start: Pos::from_row_column(0, 2),
+            end: Some(Pos::from_row_column(0, 2)),
+        };
+        for (input, expected_content, expected_col) in &[
+            (EditorInputEvent::Char('x'), "abxcd", 3),
+            (EditorInputEvent::Backspace, "acd", 1),
+            (EditorInputEvent::Del, "abd", 2),
+        ] {
+            let mut content = EditorContent::<usize>::new(80);
+            let mut editor = Editor::new(&mut content);
+            content.init_with("abcd");
+            editor.set_selection_save_col(degenerate);
+
+            editor.handle_input_undoable(*input, InputModifiers::none(), &mut content);
+            assert_eq!(content.get_content(), *expected_content);
+            assert_eq!(
+                editor.get_selection(),
+                Selection::single_r_c(0, *expected_col)
+            );
+            editor.undo(&mut content);
+            assert_markup(&editor, &content, "ab█cd");
+        }
+    }
+
+    #[test]
+    fn test_take_changed_lines_after_single_line_edit() {
+        let (mut editor, mut content) = build("a = 1\nb = 2\nc = a + b");
+        assert_eq!(content.take_changed_lines().len(), 3);
+        assert_eq!(content.take_changed_lines(), vec![]);
+
+        editor.set_cursor_pos_r_c(1, 5);
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('0'),
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(content.take_changed_lines(), vec![(1, "b = 20".to_owned())]);
+        assert_eq!(content.line_count(), 3);
+        assert_eq!(content.take_changed_lines(), vec![]);
+    }
+
+    #[test]
+    fn test_take_changed_lines_after_multiline_paste() {
+        let (mut editor, mut content) = build("a = 1\nb = 2\nc = a + b");
+        content.take_changed_lines();
+
+        editor.set_cursor_pos_r_c(1, 0);
+        editor.insert_text_undoable("x = 3\ny = 4\n", &mut content);
+        assert_eq!(
+            content.get_content(),
+            "a = 1\nx = 3\ny = 4\nb = 2\nc = a + b"
+        );
+        // the rows after the inserted ones have a new index
+        assert_eq!(
+            content.take_changed_lines(),
+            vec![
+                (1, "x = 3".to_owned()),
+                (2, "y = 4".to_owned()),
+                (3, "b = 2".to_owned()),
+                (4, "c = a + b".to_owned()),
+            ]
+        );
+        assert_eq!(content.line_count(), 5);
+    }
+
+    #[test]
+    fn test_take_changed_lines_after_swap_and_removal() {
+        let (mut editor, mut content) = build("a = 1\nb = 2\nc = 3\nd = a + b");
+        content.take_changed_lines();
+
+        editor.set_cursor_pos_r_c(1, 0);
+        editor.handle_input_undoable(EditorInputEvent::Down, InputModifiers::alt(), &mut content);
+        assert_eq!(content.get_content(), "a = 1\nc = 3\nb = 2\nd = a + b");
+        assert_eq!(
+            content.take_changed_lines(),
+            vec![(1, "c = 3".to_owned()), (2, "b = 2".to_owned())]
+        );
+        assert_eq!(content.line_count(), 4);
+
+        editor.set_cursor_pos_r_c(1, 0);
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('x'),
+            InputModifiers::ctrl(),
+            &mut content,
+        );
+        assert_eq!(content.get_content(), "a = 1\nb = 2\nd = a + b");
+        assert_eq!(
+            content.take_changed_lines(),
+            vec![(1, "b = 2".to_owned()), (2, "d = a + b".to_owned())]
+        );
+        assert_eq!(content.line_count(), 3);
+
+        // removing the last row does not move any other row
+        content.remove_line_at(2);
+        assert_eq!(content.get_content(), "a = 1\nb = 2");
+        assert_eq!(content.take_changed_lines(), vec![]);
+        assert_eq!(content.line_count(), 2);
+    }
+
+    #[test]
+    fn test_expand_selection_to_full_lines() {
+        let (mut editor, content) = build("first\nsecond\nthird");
+
+        editor.set_selection_save_col(Selection::range(
+            Pos::from_row_column(0, 1),
+            Pos::from_row_column(0, 3),
+        ));
+        editor.expand_selection_to_full_lines(&content);
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(0, 0), Pos::from_row_column(1, 0))
+        );
+
+        editor.set_selection_save_col(Selection::range(
+            Pos::from_row_column(2, 2),
+            Pos::from_row_column(1, 3),
+        ));
+        editor.expand_selection_to_full_lines(&content);
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(1, 0), Pos::from_row_column(2, 5))
+        );
+
+        editor.set_cursor_pos_r_c(1, 2);
+        editor.expand_selection_to_full_lines(&content);
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(1, 0), Pos::from_row_column(2, 0))
+        );
+    }
+
+    #[test]
+    fn test_set_content_preserving_cursor() {
+        let (mut editor, mut content) = build("a=1\nb = █a*2\nc=b");
+        assert_eq!(editor.cursor_offset(&content), 8);
+
+        editor.set_content_preserving_cursor("a=1\nb =  a*2\nc=b", &mut content);
+        assert_eq!(content.get_content(), "a=1\nb =  a*2\nc=b");
+        assert_eq!(editor.cursor_offset(&content), 8);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(1, 4));
+
+        editor.set_content_preserving_cursor("a=1", &mut content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 3));
+    }
+
+    #[test]
+    fn test_selection_per_line() {
+        let (mut editor, content) = build("first ❰line\nab\nthi❱rd line");
+
+        assert_eq!(
+            editor.selection_per_line(false, &content),
+            vec![(0, 6, 10), (1, 0, 2), (2, 0, 3)]
+        );
+        assert_eq!(
+            editor.selection_per_line(true, &content),
+            vec![(0, 3, 6), (1, 2, 2), (2, 3, 6)]
+        );
+
+        editor.set_cursor_pos_r_c(1, 1);
+        assert_eq!(editor.selection_per_line(false, &content), vec![(1, 1, 1)]);
+    }
+
+    #[test]
+    fn test_ctrl_del_at_eol_only_merges_by_default() {
+        let (mut editor, mut content) = build("sum(1,█\n    22, 3)");
+
+        editor.handle_input_undoable(EditorInputEvent::Del, InputModifiers::ctrl(), &mut content);
+        assert_markup(&editor, &content, "sum(1,█    22, 3)");
+    }
+
+    #[test]
+    fn test_ctrl_del_crosses_line() {
+        let (mut editor, mut content) = build("sum(1,█\n    22, 3)");
+        editor.set_ctrl_del_crosses_line(true);
+
+        editor.handle_input_undoable(EditorInputEvent::Del, InputModifiers::ctrl(), &mut content);
+        assert_markup(&editor, &content, "sum(1,█, 3)");
+
+        editor.undo(&mut content);
+        assert_markup(&editor, &content, "sum(1,█\n    22, 3)");
+
+        // without leading whitespace
+        content.init_with("a\nbc d");
+        editor.set_cursor_pos_r_c(0, 1);
+        editor.handle_input_undoable(EditorInputEvent::Del, InputModifiers::ctrl(), &mut content);
+        assert_eq!(content.get_content(), "a d");
+    }
+
+    #[test]
+    fn test_selection_is_forward() {
+        let (mut editor, _content) = build("first\nsecond");
+
+        editor.set_selection_save_col(Selection::range(
+            Pos::from_row_column(0, 1),
+            Pos::from_row_column(1, 0),
+        ));
+        assert_eq!(editor.selection_is_forward(), Some(true));
+
+        editor.set_selection_save_col(Selection::range(
+            Pos::from_row_column(1, 0),
+            Pos::from_row_column(0, 1),
+        ));
+        assert_eq!(editor.selection_is_forward(), Some(false));
+
+        editor.set_cursor_pos_r_c(1, 2);
+        assert_eq!(editor.selection_is_forward(), None);
+    }
+
+    #[test]
+    fn test_join_lines_raw() {
+        let (mut editor, mut content) = build("12 + 3\n  4█ * 5\nlast");
+
+        assert!(editor.join_lines_raw_undoable(&mut content).is_some());
+        assert_markup(&editor, &content, "12 + 3█  4 * 5\nlast");
+
+        editor.undo(&mut content);
+        assert_markup(&editor, &content, "12 + 3\n  4█ * 5\nlast");
+
+        editor.set_cursor_pos_r_c(0, 2);
+        assert!(editor.join_lines_raw_undoable(&mut content).is_none());
+    }
+
+    #[test]
+    fn test_get_char_opt() {
+        let (_editor, content) = build("abc\n\nd");
+
+        assert_eq!(content.get_char_opt(0, 0), Some('a'));
+        assert_eq!(content.get_char_opt(0, 2), Some('c'));
+        assert_eq!(content.get_char_opt(2, 0), Some('d'));
+        assert_eq!(content.get_char_opt(0, 3), None);
+        assert_eq!(content.get_char_opt(1, 0), None);
+        assert_eq!(content.get_char_opt(3, 0), None);
+        assert_eq!(content.get_char_opt(0, 200), None);
+    }
+
+    fn assert_edits_reach(original: &str, target: &str) -> Vec<Edit> {
+        let mut content = EditorContent::<usize>::new(80);
+        let mut editor = Editor::new(&mut content);
+        content.init_with(original);
+        let edits = content.edits_to_reach(target);
+        for edit in &edits {
+            editor.apply_edit(edit.offset, edit.removed_len, &edit.text, &mut content);
+        }
+        assert_eq!(content.get_content(), target);
+        edits
+    }
+
+    #[test]
+    fn test_edits_to_reach_insert() {
+        let edits = assert_edits_reach("a\nb\nc", "a\nx\nb\nc");
+        assert_eq!(
+            edits,
+            vec![Edit {
+                offset: 2,
+                removed_len: 0,
+                text: "x\n".to_owned()
+            }]
+        );
+        assert_edits_reach("a\nb", "a\nb\nc\nd");
+        assert_edits_reach("a\nb", "x\na\nb");
+    }
+
+    #[test]
+    fn test_edits_to_reach_delete() {
+        let edits = assert_edits_reach("a\nb\nc", "a\nc");
+        assert_eq!(
+            edits,
+            vec![Edit {
+                offset: 2,
+                removed_len: 2,
+                text: "".to_owned()
+            }]
+        );
+        assert_edits_reach("a\nb\nc", "a");
+        assert_edits_reach("a\nb\nc", "c");
+    }
+
+    #[test]
+    fn test_edits_to_reach_replace() {
+        let edits =
+            assert_edits_reach("a = 1\nb = 2\nc = 3\nd = 4", "a = 1\nb = 20\nc = 3\nd = 40");
+        assert_eq!(
+            edits,
+            vec![
+                Edit {
+                    offset: 18,
+                    removed_len: 5,
+                    text: "d = 40".to_owned()
+                },
+                Edit {
+                    offset: 6,
+                    removed_len: 5,
+                    text: "b = 20".to_owned()
+                },
+            ]
+        );
+        assert_edits_reach("a\nb\nc", "x\ny");
+        assert_edits_reach("a\nb\nc", "a\nb\nc");
+        assert!(assert_edits_reach("same", "same").is_empty());
+    }
+
+    #[test]
+    fn test_single_line_mode_enter_submits() {
+        let (mut editor, mut content) = build("search█ term");
+        editor.set_single_line(true);
+
+        let modif = editor.handle_input_undoable(
+            EditorInputEvent::Enter,
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(modif, None);
+        assert_eq!(editor.last_input_effect(), InputEffect::Submit);
+        assert_eq!(content.get_content(), "search term");
+        assert_eq!(content.line_count(), 1);
+    }
+
+    #[test]
+    fn test_single_line_mode_paste() {
+        let (mut editor, mut content) = build("name: █");
+        editor.set_single_line(true);
+
+        editor.insert_text_undoable("first\r\nsecond\nthird", &mut content);
+        assert_eq!(content.get_content(), "name: first second third");
+        assert_eq!(content.line_count(), 1);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 24));
+    }
+
+    #[test]
+    fn test_editor_cursor_state() {
+        let (mut editor, _content) = build("first\nsecond");
+        editor.set_selection_save_col(Selection::range(
+            Pos::from_row_column(1, 4),
+            Pos::from_row_column(0, 2),
+        ));
+
+        assert_eq!(
+            editor.editor_cursor_state(),
+            CursorState {
+                row: 0,
+                column: 2,
+                sel_start_row: 1,
+                sel_start_col: 4,
+                sel_end_row: 0,
+                sel_end_col: 2,
+                has_selection: true,
+            }
+        );
+
+        editor.set_cursor_pos_r_c(1, 3);
+        assert_eq!(
+            editor.editor_cursor_state(),
+            CursorState {
+                row: 1,
+                column: 3,
+                sel_start_row: 1,
+                sel_start_col: 3,
+                sel_end_row: 1,
+                sel_end_col: 3,
+                has_selection: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_input_spec() {
+        assert_eq!(
+            EditorInputEvent::parse_spec("C-S-Left"),
+            Some((
+                EditorInputEvent::Left,
+                InputModifiers {
+                    shift: true,
+                    ctrl: true,
+                    alt: false
+                }
+            ))
+        );
+        assert_eq!(
+            EditorInputEvent::parse_spec("Enter"),
+            Some((EditorInputEvent::Enter, InputModifiers::none()))
+        );
+        assert_eq!(
+            EditorInputEvent::parse_spec("a"),
+            Some((EditorInputEvent::Char('a'), InputModifiers::none()))
+        );
+        assert_eq!(
+            EditorInputEvent::parse_spec("-"),
+            Some((EditorInputEvent::Char('-'), InputModifiers::none()))
+        );
+        assert_eq!(
+            EditorInputEvent::parse_spec("C-a"),
+            Some((EditorInputEvent::Char('a'), InputModifiers::ctrl()))
+        );
+        assert_eq!(EditorInputEvent::parse_spec("Foo"), None);
+        assert_eq!(EditorInputEvent::parse_spec("X-Left"), None);
+        assert_eq!(EditorInputEvent::parse_spec(""), None);
+    }
+
+    #[test]
+    fn test_handle_input_str() {
+        let (mut editor, mut content) = build("first word█");
+
+        assert!(editor.handle_input_str("C-S-Left", &mut content).is_ok());
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(0, 10), Pos::from_row_column(0, 6))
+        );
+        assert!(editor
+            .handle_input_str("x", &mut content)
+            .unwrap()
+            .is_some());
+        assert!(editor.handle_input_str("Enter", &mut content).is_ok());
+        assert_eq!(content.get_content(), "first x\n");
+        assert!(editor.handle_input_str("Unknown", &mut content).is_err());
+        assert_eq!(content.get_content(), "first x\n");
+    }
+
+    #[test]
+    fn test_ctrl_backspace_then_up_uses_the_new_column() {
+        let (mut editor, mut content) = build("abcdefghijkl█\nab cd");
+        editor.handle_input_undoable(EditorInputEvent::Down, InputModifiers::none(), &mut content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(1, 5));
+
+        editor.handle_input_undoable(
+            EditorInputEvent::Backspace,
+            InputModifiers::ctrl(),
+            &mut content,
+        );
+        assert_eq!(content.get_content(), "abcdefghijkl\nab ");
+        editor.handle_input_undoable(EditorInputEvent::Up, InputModifiers::none(), &mut content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 3));
+    }
+
+    #[test]
+    fn test_del_then_down_uses_the_new_column() {
+        let (mut editor, mut content) = build("abcdefghijkl█\nab\ncd\nabcdefghijkl");
+        editor.handle_input_undoable(EditorInputEvent::Down, InputModifiers::none(), &mut content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(1, 2));
+
+        for modifiers in &[InputModifiers::none(), InputModifiers::ctrl()] {
+            editor.handle_input_undoable(EditorInputEvent::Del, *modifiers, &mut content);
+            assert_eq!(content.get_content(), "abcdefghijkl\nabcd\nabcdefghijkl");
+            editor.handle_input_undoable(
+                EditorInputEvent::Down,
+                InputModifiers::none(),
+                &mut content,
+            );
+            assert_eq!(editor.get_selection(), Selection::single_r_c(2, 2));
+            editor.undo(&mut content);
+            editor.set_cursor_pos_r_c(0, 12);
+            editor.handle_input_undoable(
+                EditorInputEvent::Down,
+                InputModifiers::none(),
+                &mut content,
+            );
+        }
+    }
+
+    #[test]
+    fn test_swap_lines_then_down_uses_the_current_column() {
+        let (mut editor, mut content) = build("abcdefghijkl█\nab\ncd\nabcdefghijkl");
+        editor.handle_input_undoable(EditorInputEvent::Down, InputModifiers::none(), &mut content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(1, 2));
+
+        editor.handle_input_undoable(
+            EditorInputEvent::Down,
+            InputModifiers {
+                shift: true,
+                ctrl: true,
+                alt: false,
+            },
+            &mut content,
+        );
+        assert_markup(&editor, &content, "abcdefghijkl\ncd\nab█\nabcdefghijkl");
+        editor.handle_input_undoable(EditorInputEvent::Down, InputModifiers::none(), &mut content);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(3, 2));
+    }
+
+    #[test]
+    fn test_get_selected_lines_with_indices() {
+        let (mut editor, content) = build("first\nse█cond\nthird\nfourth");
+        assert!(editor.get_selected_lines_with_indices(&content).is_empty());
+
+        editor.set_selection_save_col(Selection::range(
+            Pos::from_row_column(3, 3),
+            Pos::from_row_column(1, 2),
+        ));
+        assert_eq!(
+            editor.get_selected_lines_with_indices(&content),
+            vec![
+                (1, "cond".to_owned()),
+                (2, "third".to_owned()),
+                (3, "fou".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cursor_in_quotes() {
+        let (mut editor, content) = build("a █= \"text \\\" more\" + b");
+        assert!(!editor.cursor_in_quotes(&content));
+        editor.set_cursor_pos_r_c(0, 4);
+        assert!(!editor.cursor_in_quotes(&content));
+        editor.set_cursor_pos_r_c(0, 5);
+        assert!(editor.cursor_in_quotes(&content));
+        // the escaped quote does not close the string
+        editor.set_cursor_pos_r_c(0, 13);
+        assert!(editor.cursor_in_quotes(&content));
+        editor.set_cursor_pos_r_c(0, 19);
+        assert!(!editor.cursor_in_quotes(&content));
+        editor.set_cursor_pos_r_c(0, 22);
+        assert!(!editor.cursor_in_quotes(&content));
+    }
+
+    #[test]
+    fn test_tidy_current_line_removes_blank_line() {
+        let (mut editor, mut content) = build("first\n  █ \nthird");
+        editor.tidy_current_line_undoable(&mut content);
+        assert_markup(&editor, &content, "first\n█third");
+
+        // the last line is merged into the previous one
+        content.init_with("first\n  ");
+        editor.set_cursor_pos_r_c(1, 1);
+        editor.tidy_current_line_undoable(&mut content);
+        assert_markup(&editor, &content, "first█");
+    }
+
+    #[test]
+    fn test_tidy_current_line_trims_trailing_whitespace() {
+        let (mut editor, mut content) = build("first\n  12 + 3 █ \t \nthird");
+        editor.tidy_current_line_undoable(&mut content);
+        assert_markup(&editor, &content, "first\n  12 + 3█\nthird");
+
+        editor.set_cursor_pos_r_c(1, 3);
+        assert!(editor.tidy_current_line_undoable(&mut content).is_none());
+        assert_markup(&editor, &content, "first\n  1█2 + 3\nthird");
+    }
+
+    #[test]
+    fn test_select_to_matching_bracket_nested_on_one_line() {
+        let (mut editor, content) = build("x = █(1 + (2 * 3)) / [4]");
+        assert!(editor.select_to_matching_bracket(&content));
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(0, 4), Pos::from_row_column(0, 17))
+        );
+        assert_eq!(
+            editor.get_selected_text(&content),
+            Some("(1 + (2 * 3))".to_owned())
+        );
+
+        // the bracket right before the cursor is used, the selection points backwards
+        editor.set_cursor_pos_r_c(0, 17);
+        assert!(editor.select_to_matching_bracket(&content));
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(0, 17), Pos::from_row_column(0, 4))
+        );
+
+        editor.set_cursor_pos_r_c(0, 2);
+        assert!(!editor.select_to_matching_bracket(&content));
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 2));
+    }
+
+    #[test]
+    fn test_select_to_matching_bracket_across_lines() {
+        let (mut editor, mut content) = build("m = █[1, (2\n\n3), 4\n] * 2");
+        assert!(editor.select_to_matching_bracket(&content));
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(0, 4), Pos::from_row_column(3, 1))
+        );
+
+        editor.set_cursor_pos_r_c(3, 0);
+        assert!(editor.select_to_matching_bracket(&content));
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(3, 1), Pos::from_row_column(0, 4))
+        );
+
+        // unmatched
+        content.init_with("(1 + (2\n3)");
+        editor.set_cursor_pos_r_c(0, 0);
+        assert!(!editor.select_to_matching_bracket(&content));
+    }
+
+    #[test]
+    fn test_wrapped_segment_text() {
+        let mut content = EditorContent::<usize>::new(80);
+        content.init_with("first\nabc defgh ijklmnopq rs");
+        assert_eq!(content.wrapped_segment_text(1, 0, 8), "abc ");
+        assert_eq!(content.wrapped_segment_text(1, 1, 8), "defgh ");
+        assert_eq!(content.wrapped_segment_text(1, 2, 8), "ijklmnop");
+        assert_eq!(content.wrapped_segment_text(1, 3, 8), "q rs");
+        assert_eq!(content.wrapped_segment_text(1, 4, 8), "");
+
+        assert_eq!(content.wrapped_segment_text(0, 0, 8), "first");
+        assert_eq!(content.wrapped_segment_text(0, 1, 8), "");
+    }
+
+    #[test]
+    fn test_clamp_selection() {
+        let (editor, content) = build("first\nsecond\nthird");
+
+        assert_eq!(
+            editor.clamp_selection(
+                Selection::range(Pos::from_row_column(0, 30), Pos::from_row_column(7, 2)),
+                &content
+            ),
+            Selection::range(Pos::from_row_column(0, 5), Pos::from_row_column(2, 2))
+        );
+        assert_eq!(
+            editor.clamp_selection(Selection::single_r_c(1, 99), &content),
+            Selection::single_r_c(1, 6)
+        );
+        // both ends land on the end of the last line
+        assert_eq!(
+            editor.clamp_selection(
+                Selection::range(Pos::from_row_column(5, 10), Pos::from_row_column(9, 9)),
+                &content
+            ),
+            Selection::single_r_c(2, 5)
+        );
+        let valid = Selection::range(Pos::from_row_column(1, 3), Pos::from_row_column(0, 1));
+        assert_eq!(editor.clamp_selection(valid, &content), valid);
+    }
+
+    #[test]
+    fn test_del_multiline_selection_near_max_line_len_keeps_buffer_consistent() {
+        fn assert_consistent(content: &EditorContent<usize>) {
+            for row in 0..content.line_count() {
+                let line = content.get_line_valid_chars(row);
+                assert_eq!(line.len(), content.line_len(row));
+                assert!(line.len() <= content.max_line_len());
+                assert!(!line.contains(&(0 as char)));
+            }
+        }
+        let mut content = EditorContent::<usize>::new(10);
+        let mut editor = Editor::new(&mut content);
+        let text = "aaaaaaaaaa\nbbbbbbbbbb\ncccccccccc\ndddddddddd\neeeeeeeeee";
+        content.init_with(text);
+
+        // the merged line (3 + 7 chars) fills the line completely
+        editor.set_selection_save_col(Selection::range(
+            Pos::from_row_column(0, 3),
+            Pos::from_row_column(3, 3),
+        ));
+        editor.handle_input_undoable(EditorInputEvent::Del, InputModifiers::none(), &mut content);
+        assert_eq!(content.get_content(), "aaaddddddd\neeeeeeeeee");
+        assert_eq!(content.line_len(0), 10);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 3));
+        assert_consistent(&content);
+
+        editor.undo(&mut content);
+        assert_eq!(content.get_content(), text);
+        assert_consistent(&content);
+
+        // the merged line (8 + 9 chars) would not fit, nothing happens
+        editor.set_selection_save_col(Selection::range(
+            Pos::from_row_column(0, 8),
+            Pos::from_row_column(2, 1),
+        ));
+        editor.handle_input_undoable(EditorInputEvent::Del, InputModifiers::none(), &mut content);
+        assert_eq!(content.get_content(), text);
+        assert_consistent(&content);
+
+        // selection starting at the beginning of a line
+        editor.set_selection_save_col(Selection::range(
+            Pos::from_row_column(1, 0),
+            Pos::from_row_column(4, 9),
+        ));
+        editor.handle_input_undoable(EditorInputEvent::Del, InputModifiers::none(), &mut content);
+        assert_eq!(content.get_content(), "aaaaaaaaaa\ne");
+        assert_consistent(&content);
+    }
+
+    #[test]
+    fn test_keystroke_resets_blink() {
+        let (mut editor, mut content) = build("abc");
+        editor.handle_tick(0);
+        assert!(editor.is_cursor_shown());
+        assert!(editor.handle_tick(EDITOR_CURSOR_TICK_MS));
+        assert!(!editor.is_cursor_shown());
+
+        // even a keystroke which does nothing shows the cursor
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('z'),
+            InputModifiers::ctrl(),
+            &mut content,
+        );
+        assert!(editor.is_cursor_shown());
+        // and the blinking period is restarted
+        assert!(!editor.handle_tick(EDITOR_CURSOR_TICK_MS * 2 - 1));
+        assert!(editor.is_cursor_shown());
+        assert!(editor.handle_tick(EDITOR_CURSOR_TICK_MS * 2));
+        assert!(!editor.is_cursor_shown());
+
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('d'),
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert!(editor.is_cursor_shown());
+    }
+
+    #[test]
+    fn test_select_inside_quotes_on_one_line() {
+        let (mut editor, content) = build("a = \"x█ \\\" y\" + \"z\"");
+        assert!(editor.select_inside_quotes(false, &content));
+        assert_eq!(
+            editor.get_selected_text(&content),
+            Some("x \\\" y".to_owned())
+        );
+
+        // between two string literals
+        editor.set_cursor_pos_r_c(0, 14);
+        assert!(!editor.select_inside_quotes(false, &content));
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 14));
+    }
+
+    #[test]
+    fn test_select_inside_quotes_across_lines() {
+        let (mut editor, mut content) = build("s = \"first line\nse█cond\" line\nthird");
+        assert!(!editor.select_inside_quotes(false, &content));
+        assert!(editor.select_inside_quotes(true, &content));
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(0, 5), Pos::from_row_column(1, 6))
+        );
+        assert_eq!(
+            editor.get_selected_text(&content),
+            Some("first line\nsecond".to_owned())
+        );
+
+        editor.set_cursor_pos_r_c(0, 8);
+        assert!(editor.select_inside_quotes(true, &content));
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(0, 5), Pos::from_row_column(1, 6))
+        );
+
+        editor.set_cursor_pos_r_c(2, 2);
+        assert!(!editor.select_inside_quotes(true, &content));
+
+        // no closing quote
+        content.init_with("s = \"first line\nsecond line");
+        editor.set_cursor_pos_r_c(1, 2);
+        assert!(!editor.select_inside_quotes(true, &content));
+    }
+
+    #[test]
+    fn test_block_selection_widths() {
+        let (mut editor, content) = build("ab█cdefgh\nabc\n\nabcdef\nabcdefghij");
+        assert_eq!(editor.block_selection_widths(&content), None);
+
+        editor.set_selection_save_col(Selection::range(
+            Pos::from_row_column(4, 7),
+            Pos::from_row_column(0, 2),
+        ));
+        assert_eq!(
+            editor.block_selection_widths(&content),
+            Some(vec![5, 1, 0, 4, 5])
+        );
+    }
+
+    #[test]
+    fn test_insert_soft_break() {
+        let (mut editor, mut content) = build("first\nabc█defgh\nthird");
+        assert!(editor.insert_soft_break(&mut content));
+        assert!(!editor.insert_soft_break(&mut content));
+        assert_eq!(content.get_content(), "first\nabcdefgh\nthird");
+        assert_eq!(content.line_count(), 3);
+        assert_eq!(content.soft_breaks(1), &[3]);
+        assert!(content.soft_breaks(0).is_empty());
+
+        // not inside the line
+        editor.set_cursor_pos_r_c(1, 0);
+        assert!(!editor.insert_soft_break(&mut content));
+        editor.set_cursor_pos_r_c(1, 8);
+        assert!(!editor.insert_soft_break(&mut content));
+        assert_eq!(content.soft_breaks(1), &[3]);
+    }
+
+    #[test]
+    fn test_soft_breaks_follow_the_edits() {
+        let (mut editor, mut content) = build("first\nabc█defgh\nthird");
+        editor.insert_soft_break(&mut content);
+        editor.set_cursor_pos_r_c(1, 6);
+        editor.insert_soft_break(&mut content);
+
+        // typing before the break moves it
+        editor.set_cursor_pos_r_c(1, 1);
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('X'),
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(content.soft_breaks(1), &[4, 7]);
+        // typing at the break puts the text after the break
+        editor.set_cursor_pos_r_c(1, 4);
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('Y'),
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(
+            content.get_line_valid_chars(1).iter().collect::<String>(),
+            "aXbcYdefgh"
+        );
+        assert_eq!(content.soft_breaks(1), &[4, 8]);
+        editor.handle_input_undoable(
+            EditorInputEvent::Backspace,
+            InputModifiers::none(),
+            &mut content,
+        );
+        editor.handle_input_undoable(
+            EditorInputEvent::Backspace,
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(content.soft_breaks(1), &[3, 6]);
+
+        // the breaks move with their row
+        editor.set_cursor_pos_r_c(0, 0);
+        editor.handle_input_undoable(
+            EditorInputEvent::Enter,
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(content.soft_breaks(2), &[3, 6]);
+        assert!(content.soft_breaks(1).is_empty());
+
+        // breaks after the end of the line are dropped
+        editor.set_selection_save_col(Selection::range(
+            Pos::from_row_column(2, 4),
+            Pos::from_row_column(2, 8),
+        ));
+        editor.handle_input_undoable(EditorInputEvent::Del, InputModifiers::none(), &mut content);
+        assert_eq!(content.soft_breaks(2), &[3]);
+    }
+
+    #[test]
+    fn test_bookmarks() {
+        let (mut editor, mut content) = build("a\nb\nc\nd\ne");
+        assert!(!editor.next_bookmark(&content));
+
+        editor.set_cursor_pos_r_c(1, 1);
+        editor.toggle_bookmark(&mut content);
+        editor.set_cursor_pos_r_c(3, 0);
+        editor.toggle_bookmark(&mut content);
+        assert_eq!(content.bookmarks(), vec![1, 3]);
+
+        // inserting a line above shifts them
+        editor.set_cursor_pos_r_c(0, 0);
+        editor.handle_input_undoable(
+            EditorInputEvent::Enter,
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(content.bookmarks(), vec![2, 4]);
+
+        editor.set_cursor_pos_r_c(0, 0);
+        assert!(editor.next_bookmark(&content));
+        assert_eq!(editor.get_selection(), Selection::single_r_c(2, 0));
+        assert!(editor.next_bookmark(&content));
+        assert_eq!(editor.get_selection(), Selection::single_r_c(4, 0));
+        assert!(editor.next_bookmark(&content));
+        assert_eq!(editor.get_selection(), Selection::single_r_c(2, 0));
+        assert!(editor.prev_bookmark(&content));
+        assert_eq!(editor.get_selection(), Selection::single_r_c(4, 0));
+        assert!(editor.prev_bookmark(&content));
+        assert_eq!(editor.get_selection(), Selection::single_r_c(2, 0));
+
+        // toggling again removes it
+        editor.toggle_bookmark(&mut content);
+        assert_eq!(content.bookmarks(), vec![4]);
+    }
+
+    #[test]
+    fn test_bookmark_is_dropped_with_its_line() {
+        let (mut editor, mut content) = build("a\n█b\nc\nd");
+        editor.toggle_bookmark(&mut content);
+        editor.set_cursor_pos_r_c(3, 0);
+        editor.toggle_bookmark(&mut content);
+
+        editor.set_selection_save_col(Selection::range(
+            Pos::from_row_column(0, 1),
+            Pos::from_row_column(1, 1),
+        ));
+        editor.handle_input_undoable(EditorInputEvent::Del, InputModifiers::none(), &mut content);
+        assert_eq!(content.get_content(), "a\nc\nd");
+        assert_eq!(content.bookmarks(), vec![2]);
+    }
+
+    #[test]
+    fn test_scroll_selection_into_view_above_the_viewport() {
+        let (mut editor, mut content) = build("");
+        content.init_with(&"line\n".repeat(100));
+        editor.set_viewport_height(20);
+        editor.set_scroll_top(50);
+        editor.set_selection_save_col(Selection::range(
+            Pos::from_row_column(12, 0),
+            Pos::from_row_column(10, 2),
+        ));
+        editor.scroll_selection_into_view();
+        assert_eq!(editor.scroll_top(), 10);
+        // already visible
+        editor.set_scroll_top(5);
+        editor.scroll_selection_into_view();
+        assert_eq!(editor.scroll_top(), 5);
+
+        // does not fit, the cursor side is shown
+        editor.set_scroll_top(50);
+        editor.set_selection_save_col(Selection::range(
+            Pos::from_row_column(40, 0),
+            Pos::from_row_column(10, 2),
+        ));
+        editor.scroll_selection_into_view();
+        assert_eq!(editor.scroll_top(), 10);
+    }
+
+    #[test]
+    fn test_scroll_selection_into_view_below_the_viewport() {
+        let (mut editor, mut content) = build("");
+        content.init_with(&"line\n".repeat(100));
+        editor.set_viewport_height(20);
+        editor.set_selection_save_col(Selection::range(
+            Pos::from_row_column(60, 0),
+            Pos::from_row_column(64, 2),
+        ));
+        editor.scroll_selection_into_view();
+        assert_eq!(editor.scroll_top(), 45);
+        // the whole selection is shown even if the cursor is already visible
+        editor.set_viewport_height(12);
+        editor.set_scroll_top(50);
+        editor.scroll_selection_into_view();
+        assert_eq!(editor.scroll_top(), 53);
+
+        // does not fit, the cursor side is shown
+        editor.set_viewport_height(20);
+        editor.set_scroll_top(0);
+        editor.set_selection_save_col(Selection::range(
+            Pos::from_row_column(60, 0),
+            Pos::from_row_column(90, 2),
+        ));
+        editor.scroll_selection_into_view();
+        assert_eq!(editor.scroll_top(), 71);
+
+        // without a known viewport nothing is scrolled
+        editor.set_viewport_height(0);
+        editor.set_scroll_top(0);
+        editor.scroll_selection_into_view();
+        assert_eq!(editor.scroll_top(), 0);
+    }
+
+    #[test]
+    fn test_char_class_at() {
+        let mut content = EditorContent::<usize>::new(80);
+        content.init_with("a_1 \"x\" + y\n");
+        let class_at = |col| content.char_class_at(Pos::from_row_column(0, col));
+        assert_eq!(class_at(0), Some(CharClass::Word));
+        assert_eq!(class_at(1), Some(CharClass::Word));
+        assert_eq!(class_at(2), Some(CharClass::Word));
+        assert_eq!(class_at(3), Some(CharClass::Whitespace));
+        assert_eq!(class_at(4), Some(CharClass::Quote));
+        assert_eq!(class_at(8), Some(CharClass::Symbol));
+        // after the end of the line
+        assert_eq!(class_at(11), None);
+        assert_eq!(content.char_class_at(Pos::from_row_column(5, 0)), None);
+    }
+
+    #[test]
+    fn test_cut_selection() {
+        let (mut editor, mut content) = build("first\nse█cond\nthird");
+        assert_eq!(editor.cut_selection(&mut content), None);
+        assert_eq!(content.get_content(), "first\nsecond\nthird");
+
+        editor.set_selection_save_col(Selection::range(
+            Pos::from_row_column(2, 2),
+            Pos::from_row_column(0, 3),
+        ));
+        let expected = editor.get_selected_text(&content);
+        assert_eq!(editor.cut_selection(&mut content), expected);
+        assert_eq!(expected, Some("st\nsecond\nth".to_owned()));
+        assert_markup(&editor, &content, "fir█ird");
+
+        editor.undo(&mut content);
+        assert_eq!(content.get_content(), "first\nsecond\nthird");
+    }
+
+    #[test]
+    fn test_ctrl_x_cuts_the_selection() {
+        let (mut editor, mut content) = build("first\ns❱eco❰nd\nthird");
+        let modif_type = editor.handle_input_undoable(
+            EditorInputEvent::Char('x'),
+            InputModifiers::ctrl(),
+            &mut content,
+        );
+        assert_eq!(modif_type, Some(RowModificationType::SingleLine(1)));
+        assert_eq!(content.get_content(), "first\nsnd\nthird");
+        assert_eq!(editor.clipboard, "eco");
+        assert_eq!(editor.get_selection(), Selection::single_r_c(1, 1));
+    }
+
+    #[test]
+    fn test_ctrl_shift_enter_inserts_line_above_without_moving_to_it() {
+        let (mut editor, mut content) = build("first\nsec█ond\nthird");
+        editor.handle_input_undoable(
+            EditorInputEvent::Enter,
+            InputModifiers::ctrl_shift(),
+            &mut content,
+        );
+        assert_markup(&editor, &content, "first\n\nsec█ond\nthird");
+
+        // typing continues in the original line
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('X'),
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(content.get_content(), "first\n\nsecXond\nthird");
+
+        editor.undo(&mut content);
+        editor.undo(&mut content);
+        assert_markup(&editor, &content, "first\nsec█ond\nthird");
+    }
+
+    #[test]
+    fn test_ctrl_shift_enter_keeps_the_selection() {
+        let (mut editor, mut content) = build("first\ns❱econd\nth❰ird");
+        editor.handle_input_undoable(
+            EditorInputEvent::Enter,
+            InputModifiers::ctrl_shift(),
+            &mut content,
+        );
+        assert_markup(&editor, &content, "first\n\ns❱econd\nth❰ird");
+    }
+
+    fn fifty_lines() -> String {
+        (0..50)
+            .map(|i| format!("line {}", i))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_simple_page_up_cursor() {
+        let (mut editor, mut content) = build("");
+        content.init_with(&fifty_lines());
+        editor.set_cursor_pos_r_c(45, 7);
+        editor.handle_input_undoable(
+            EditorInputEvent::PageUp,
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(editor.get_selection(), Selection::single_r_c(25, 7));
+        editor.handle_input_undoable(
+            EditorInputEvent::PageUp,
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(editor.get_selection(), Selection::single_r_c(5, 6));
+        // like every other cursor movement, a page movement saves the column
+        editor.handle_input_undoable(
+            EditorInputEvent::PageDown,
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(editor.get_selection(), Selection::single_r_c(25, 6));
+        editor.handle_input_undoable(
+            EditorInputEvent::PageUp,
+            InputModifiers::none(),
+            &mut content,
+        );
+        // at the top edge
+        editor.handle_input_undoable(
+            EditorInputEvent::PageUp,
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 0));
+    }
+
+    #[test]
+    fn test_simple_page_down_cursor() {
+        let (mut editor, mut content) = build("");
+        content.init_with(&fifty_lines());
+        editor.set_cursor_pos_r_c(3, 5);
+        editor.handle_input_undoable(
+            EditorInputEvent::PageDown,
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(editor.get_selection(), Selection::single_r_c(23, 5));
+        editor.handle_input_undoable(
+            EditorInputEvent::PageDown,
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(editor.get_selection(), Selection::single_r_c(43, 5));
+        // at the bottom edge
+        editor.handle_input_undoable(
+            EditorInputEvent::PageDown,
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(editor.get_selection(), Selection::single_r_c(49, 7));
+    }
+
+    #[test]
+    fn test_page_up_down_selection() {
+        let (mut editor, mut content) = build("");
+        content.init_with(&fifty_lines());
+        editor.set_page_size(10);
+        editor.set_cursor_pos_r_c(30, 2);
+        editor.handle_input_undoable(
+            EditorInputEvent::PageUp,
+            InputModifiers::shift(),
+            &mut content,
+        );
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(30, 2), Pos::from_row_column(20, 2))
+        );
+        editor.handle_input_undoable(
+            EditorInputEvent::PageDown,
+            InputModifiers::shift(),
+            &mut content,
+        );
+        editor.handle_input_undoable(
+            EditorInputEvent::PageDown,
+            InputModifiers::shift(),
+            &mut content,
+        );
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(30, 2), Pos::from_row_column(40, 2))
+        );
+    }
+
+    #[test]
+    fn test_undo_ctrl_x_of_the_line_before_the_last_one() {
+        let mut content = EditorContent::<usize>::new(20);
+        let mut editor = Editor::new(&mut content);
+        content.init_with("a\nb\n");
+        editor.set_cursor_pos_r_c(1, 0);
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('x'),
+            InputModifiers::ctrl(),
+            &mut content,
+        );
+        assert_eq!(content.get_content(), "a\n");
+        editor.undo(&mut content);
+        assert_eq!(content.get_content(), "a\nb\n");
+
+        // the last line is only cleared
+        let mut content = EditorContent::<usize>::new(20);
+        let mut editor = Editor::new(&mut content);
+        content.init_with("a\nb");
+        editor.set_cursor_pos_r_c(1, 1);
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('x'),
+            InputModifiers::ctrl(),
+            &mut content,
+        );
+        assert_eq!(content.get_content(), "a\n");
+        editor.undo(&mut content);
+        assert_markup(&editor, &content, "a\nb█");
+    }
+
+    #[test]
+    fn test_char_count_cached_stays_in_sync_with_random_edits() {
+        // xorshift, so the edits are the same in every run
+        let mut seed: u32 = 0x2545_f491;
+        let mut next = |max: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed as usize % max
+        };
+        let inputs = [
+            EditorInputEvent::Char('a'),
+            EditorInputEvent::Char('1'),
+            EditorInputEvent::Char('+'),
+            EditorInputEvent::Enter,
+            EditorInputEvent::Backspace,
+            EditorInputEvent::Del,
+            EditorInputEvent::Tab,
+            EditorInputEvent::Left,
+            EditorInputEvent::Right,
+            EditorInputEvent::Up,
+            EditorInputEvent::Down,
+            EditorInputEvent::Home,
+            EditorInputEvent::End,
+        ];
+        let modifiers = [
+            InputModifiers::none(),
+            InputModifiers::shift(),
+            InputModifiers::ctrl(),
+            InputModifiers::ctrl_shift(),
+        ];
+        let mut content = EditorContent::<usize>::new(20);
+        let mut editor = Editor::new(&mut content);
+        content.init_with("first line\n\nsecond line\nthird");
+        assert_eq!(content.char_count_cached(), content.char_count());
+        for _ in 0..5000 {
+            match next(20) {
+                0 => {
+                    editor.insert_text_undoable("12\nab cd\n", &mut content);
+                }
+                1 => {
+                    editor.undo(&mut content);
+                }
+                2 => {
+                    editor.handle_input_undoable(
+                        EditorInputEvent::Char('x'),
+                        InputModifiers::ctrl(),
+                        &mut content,
+                    );
+                }
+                _ => {
+                    let input = inputs[next(inputs.len())];
+                    let modifiers = modifiers[next(modifiers.len())];
+                    editor.handle_input_undoable(input, modifiers, &mut content);
+                }
+            }
+            assert_eq!(content.char_count_cached(), content.char_count());
+            assert_eq!(content.char_count(), content.get_content().chars().count());
+        }
+    }
+
+    #[test]
+    fn test_ctrl_a_selects_the_whole_content() {
+        let mut content = EditorContent::<usize>::new(80);
+        let mut editor = Editor::new(&mut content);
+        content.init_with("first\n\nthe third line\n");
+        editor.set_cursor_pos_r_c(1, 0);
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('a'),
+            InputModifiers::ctrl(),
+            &mut content,
+        );
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(0, 0), Pos::from_row_column(3, 0))
+        );
+        assert_eq!(editor.view_state().last_column_index, 0);
+        assert_eq!(
+            editor.get_selected_text(&content),
+            Some(content.get_content())
+        );
+
+        content.init_with("first\nthe last line");
+        editor.select_all(&content);
+        assert_eq!(editor.view_state().last_column_index, 13);
+        assert_eq!(
+            editor.get_selected_text(&content),
+            Some("first\nthe last line".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_paste_matching_indent_into_unindented_line() {
+        let (mut editor, mut content) = build("first\n█\nlast");
+        editor.paste_matching_indent(
+            "        a = 1\n          b = 2\n\n        c = a + b",
+            &mut content,
+        );
+        assert_markup(
+            &editor,
+            &content,
+            "first\na = 1\n  b = 2\n\nc = a + b█\nlast",
+        );
+    }
+
+    #[test]
+    fn test_paste_matching_indent_into_indented_line() {
+        let (mut editor, mut content) = build("sum\n  █");
+        editor.paste_matching_indent("\ta\r\n\t\tb\r\n", &mut content);
+        assert_eq!(content.get_content(), "sum\n  a\n  \tb\n");
+    }
+
+    #[test]
+    fn test_home_toggles_between_first_non_whitespace_and_line_start() {
+        test(
+            "    abc█def",
+            &[EditorInputEvent::Home],
+            InputModifiers::none(),
+            "    █abcdef",
+        );
+        test(
+            "    abc█def",
+            &[EditorInputEvent::Home, EditorInputEvent::Home],
+            InputModifiers::none(),
+            "█    abcdef",
+        );
+        test(
+            "\t  abc█def",
+            &[
+                EditorInputEvent::Home,
+                EditorInputEvent::Home,
+                EditorInputEvent::Home,
+            ],
+            InputModifiers::none(),
+            "\t  █abcdef",
+        );
+        test(
+            "  █  abcdef",
+            &[EditorInputEvent::Home],
+            InputModifiers::none(),
+            "    █abcdef",
+        );
+    }
+
+    #[test]
+    fn test_home_on_all_whitespace_line() {
+        test(
+            "     █ ",
+            &[EditorInputEvent::Home],
+            InputModifiers::none(),
+            "█      ",
+        );
+        test(
+            "█      ",
+            &[EditorInputEvent::Home],
+            InputModifiers::none(),
+            "█      ",
+        );
+    }
+
+    #[test]
+    fn test_home_on_line_without_leading_whitespace() {
+        test(
+            "abc█def",
+            &[EditorInputEvent::Home],
+            InputModifiers::none(),
+            "█abcdef",
+        );
+        test(
+            "abc█def",
+            &[EditorInputEvent::Home, EditorInputEvent::Home],
+            InputModifiers::none(),
+            "█abcdef",
+        );
+    }
+
+    #[test]
+    fn test_shift_home_extends_to_first_non_whitespace() {
+        test(
+            "    abc█def",
+            &[EditorInputEvent::Home],
+            InputModifiers::shift(),
+            "    ❰abc❱def",
+        );
+        test(
+            "    abc█def",
+            &[EditorInputEvent::Home, EditorInputEvent::Home],
+            InputModifiers::shift(),
+            "❰    abc❱def",
+        );
+    }
+
+    #[test]
+    fn test_single_char_edit_stores_only_the_char_for_undo() {
+        let (mut editor, mut content) = build("first line\nsecond█ line\nthird line");
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('X'),
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(
+            content.get_content(),
+            "first line\nsecondX line\nthird line"
+        );
+
+        // the undo stack stores the edit itself instead of a copy of the content
+        assert_eq!(content.undo_stack.len(), 1);
+        assert_eq!(content.undo_stack[0].len(), 1);
+        assert!(matches!(
+            content.undo_stack[0][0],
+            EditorCommand::InsertChar { pos, ch: 'X' } if pos == Pos::from_row_column(1, 6)
+        ));
+
+        editor.undo(&mut content);
+        assert_markup(&editor, &content, "first line\nsecond█ line\nthird line");
+        editor.redo(&mut content);
+        assert_markup(&editor, &content, "first line\nsecondX█ line\nthird line");
+    }
+
+    #[test]
+    fn test_trailing_whitespace_edit_is_whitespace_only() {
+        let (mut editor, mut content) = build("first\n12 + 3█\nthird");
+        editor.handle_input_undoable(
+            EditorInputEvent::Char(' '),
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(editor.last_input_effect(), InputEffect::WhitespaceOnly(1));
+        editor.handle_input_undoable(EditorInputEvent::Tab, InputModifiers::none(), &mut content);
+        assert_eq!(editor.last_input_effect(), InputEffect::WhitespaceOnly(1));
+        editor.handle_input_undoable(
+            EditorInputEvent::Backspace,
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(editor.last_input_effect(), InputEffect::WhitespaceOnly(1));
+
+        // a space inside the text
+        editor.set_cursor_pos_r_c(1, 2);
+        editor.handle_input_undoable(
+            EditorInputEvent::Char(' '),
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(editor.last_input_effect(), InputEffect::TextChanged(1));
+        // non whitespace at the end
+        editor.set_cursor_pos_r_c(1, content.line_len(1));
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('4'),
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(editor.last_input_effect(), InputEffect::TextChanged(1));
+    }
+
+    #[test]
+    fn test_shift_tab_dedents_the_selected_lines() {
+        test(
+            "ab❱c\n  def\n    gh❰i",
+            &[EditorInputEvent::Tab],
+            InputModifiers::shift(),
+            "ab❱c\ndef\ngh❰i",
+        );
+        test(
+            "  a❰bc\n  def\n    gh❱i",
+            &[EditorInputEvent::Tab],
+            InputModifiers::shift(),
+            "a❰bc\ndef\ngh❱i",
+        );
+        // the columns do not go below zero, and an other tab removes the rest
+        test(
+            "  ❱  abc\nd\n      e❰f",
+            &[EditorInputEvent::Tab, EditorInputEvent::Tab],
+            InputModifiers::shift(),
+            "❱abc\nd\ne❰f",
+        );
+        // the whole dedent is undone at once
+        let (mut editor, mut content) = build("a❱bc\n  def\n    gh❰i");
+        editor.handle_input_undoable(EditorInputEvent::Tab, InputModifiers::shift(), &mut content);
+        assert_eq!(content.get_content(), "abc\ndef\nghi");
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('z'),
+            InputModifiers::ctrl(),
+            &mut content,
+        );
+        assert_eq!(content.get_content(), "abc\n  def\n    ghi");
+    }
+
+    #[test]
+    fn test_write_content_into_a_sink() {
+        let mut content = EditorContent::<usize>::new(80);
+        content.init_with("first\n\n  sec ond\nthird");
+        let mut out = String::new();
+        content.write_content(&mut out, LineEnding::Lf).unwrap();
+        assert_eq!(out, content.get_content());
+
+        let mut out = String::new();
+        content.write_content(&mut out, LineEnding::CrLf).unwrap();
+        assert_eq!(out, "first\r\n\r\n  sec ond\r\nthird");
+        assert_eq!(out, content.get_content_with(LineEnding::CrLf));
+    }
+
+    #[test]
+    fn test_alt_up_down_moves_the_line() {
+        test(
+            "first\nsec█ond\nthird",
+            &[EditorInputEvent::Up],
+            InputModifiers::alt(),
+            "sec█ond\nfirst\nthird",
+        );
+        test(
+            "first\nsec█ond\nthird",
+            &[EditorInputEvent::Down],
+            InputModifiers::alt(),
+            "first\nthird\nsec█ond",
+        );
+        // no-op at the boundaries
+        test(
+            "fi█rst\nsecond",
+            &[EditorInputEvent::Up],
+            InputModifiers::alt(),
+            "fi█rst\nsecond",
+        );
+        test(
+            "first\nsec█ond",
+            &[EditorInputEvent::Down],
+            InputModifiers::alt(),
+            "first\nsec█ond",
+        );
+        // the selected block moves together
+        test(
+            "first\nse❱cond\nthi❰rd\nfourth",
+            &[EditorInputEvent::Down],
+            InputModifiers::alt(),
+            "first\nfourth\nse❱cond\nthi❰rd",
+        );
+        test(
+            "first\nse❰cond\nthi❱rd\nfourth",
+            &[EditorInputEvent::Up, EditorInputEvent::Up],
+            InputModifiers::alt(),
+            "se❰cond\nthi❱rd\nfirst\nfourth",
+        );
+    }
+
+    #[test]
+    fn test_alt_up_keeps_the_last_column_index() {
+        let (mut editor, mut content) = build("first line\nab\nthird li█ne");
+        // the cursor is clamped to the end of the short line, but remembers its column
+        editor.handle_input_undoable(EditorInputEvent::Up, InputModifiers::none(), &mut content);
+        assert_eq!(
+            editor.get_selection().get_cursor_pos(),
+            Pos::from_row_column(1, 2)
+        );
+
+        editor.handle_input_undoable(EditorInputEvent::Up, InputModifiers::alt(), &mut content);
+        assert_eq!(content.get_content(), "ab\nfirst line\nthird line");
+        assert_eq!(
+            editor.get_selection().get_cursor_pos(),
+            Pos::from_row_column(0, 2)
+        );
+        assert_eq!(editor.view_state().last_column_index, 8);
+
+        editor.handle_input_undoable(EditorInputEvent::Down, InputModifiers::none(), &mut content);
+        assert_eq!(
+            editor.get_selection().get_cursor_pos(),
+            Pos::from_row_column(1, 8)
+        );
+    }
+
+    #[test]
+    fn test_mark_follows_external_edits() {
+        let (mut editor, mut content) = build("first\nsecond\nthird");
+        assert_eq!(editor.select_to_mark(&content), false);
+
+        editor.set_cursor_pos_r_c(1, 3);
+        editor.set_mark();
+        // an other writer inserts text before the mark
+        editor.apply_edit(2, 0, "xx\nyy", &mut content);
+        assert_eq!(content.get_content(), "fixx\nyyrst\nsecond\nthird");
+        assert_eq!(editor.mark(), Some(Pos::from_row_column(2, 3)));
+
+        editor.set_cursor_pos_r_c(3, 2);
+        assert_eq!(editor.select_to_mark(&content), true);
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(2, 3), Pos::from_row_column(3, 2))
+        );
+        assert_eq!(
+            editor.get_selected_text(&content).as_deref(),
+            Some("ond\nth")
+        );
+
+        // an edit after the mark does not move it
+        editor.apply_edit(content.char_count(), 0, "!", &mut content);
+        assert_eq!(editor.mark(), Some(Pos::from_row_column(2, 3)));
+        // the mark is clamped to the start of a removed range containing it
+        editor.apply_edit(12, 3, "", &mut content);
+        assert_eq!(content.get_content(), "fixx\nyyrst\nsnd\nthird!");
+        assert_eq!(editor.mark(), Some(Pos::from_row_column(2, 1)));
+    }
+
+    #[test]
+    fn test_transpose_lines_no_undo() {
+        let (mut editor, mut content) = build("first\nse█cond");
+
+        editor.transpose_lines_no_undo(&mut content);
+        assert_eq!(content.get_content(), "second\nfirst");
+        assert!(content.undo_stack.is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_selection_no_undo() {
+        let (mut editor, mut content) = build("a❱b❰c");
+
+        editor.duplicate_selection_no_undo(&mut content);
+        assert_eq!(content.get_content(), "abbc");
+        assert!(content.undo_stack.is_empty());
+    }
+
+    #[test]
+    fn test_join_lines_raw_no_undo() {
+        let (mut editor, mut content) = build("abc\n d█ef");
+
+        editor.join_lines_raw_no_undo(&mut content);
+        assert_eq!(content.get_content(), "abc def");
+        assert!(content.undo_stack.is_empty());
+    }
+
+    #[test]
+    fn test_tidy_current_line_no_undo() {
+        let (mut editor, mut content) = build("abc  █\ndef");
+
+        editor.tidy_current_line_no_undo(&mut content);
+        assert_eq!(content.get_content(), "abc\ndef");
+        assert!(content.undo_stack.is_empty());
+    }
+
+    #[test]
+    fn test_single_line_mode_overlong_paste_is_truncated() {
+        let mut content = EditorContent::<usize>::new(10);
+        let mut editor = Editor::new(&mut content);
+        editor.set_single_line(true);
+        content.init_with("ab");
+        editor.set_cursor_pos_r_c(0, 1);
+
+        editor.insert_text_undoable("0123456789", &mut content);
+        assert_eq!(content.get_content(), "a01234567b");
+        assert_eq!(content.line_count(), 1);
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 9));
+
+        // nothing fits into a full line
+        assert_eq!(editor.insert_text_undoable("x", &mut content), None);
+        assert_eq!(content.get_content(), "a01234567b");
+
+        // the replaced selection makes room
+        editor.set_cursor_range(Pos::from_row_column(0, 1), Pos::from_row_column(0, 5));
+        editor.insert_text_undoable("xyz\nw", &mut content);
+        assert_eq!(content.get_content(), "axyz 4567b");
+        assert_eq!(content.line_count(), 1);
+    }
+
+    #[test]
+    fn test_surround_selection_keeps_the_selection_if_nothing_fits() {
+        let mut content = EditorContent::<usize>::new(10);
+        let mut editor = Editor::new(&mut content);
+        editor.set_single_line(true);
+        content.init_with("abc defgh");
+        editor.set_cursor_range(Pos::from_row_column(0, 4), Pos::from_row_column(0, 7));
+
+        assert_eq!(editor.surround_selection("**", "**", &mut content), None);
+        assert_markup(&editor, &content, "abc ❱def❰gh");
+
+        editor.set_cursor_pos_r_c(0, 2);
+        assert_eq!(editor.surround_selection("(", ")", &mut content), None);
+        assert_markup(&editor, &content, "ab█c defgh");
+    }
+
+    #[test]
+    fn test_apply_edit_which_wraps_the_row() {
+        let mut content = EditorContent::<usize>::new(10);
+        let mut editor = Editor::new(&mut content);
+        content.init_with("abcdefgh\nxyz");
+        // "gh" is selected
+        editor.set_cursor_range(Pos::from_row_column(0, 6), Pos::from_row_column(0, 8));
+
+        editor.apply_edit(2, 0, "1234", &mut content);
+        assert_markup(&editor, &content, "ab1234cdef\n❱gh❰\nxyz");
+
+        // the rows after the edit follow the added row
+        editor.set_cursor_range(Pos::from_row_column(2, 1), Pos::from_row_column(2, 3));
+        editor.apply_edit(0, 0, "--", &mut content);
+        assert_markup(&editor, &content, "--ab1234cd\nef\ngh\nx❱yz❰");
+    }
+
+    #[test]
+    fn test_apply_edit_is_not_undoable() {
+        let (mut editor, mut content) = build("abc█");
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('d'),
+            InputModifiers::none(),
+            &mut content,
+        );
+
+        editor.apply_edit(0, 1, "X", &mut content);
+        assert_eq!(content.get_content(), "Xbcd");
+        assert!(content.undo_stack.is_empty());
+        assert!(content.redo_stack.is_empty());
+        // Ctrl-Z does not revert the change of the other writer
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('z'),
+            InputModifiers::ctrl(),
+            &mut content,
+        );
+        assert_eq!(content.get_content(), "Xbcd");
+    }
+
+    #[test]
+    fn test_drag_anchor_is_cleared_on_mouse_up_and_on_change() {
+        let (mut editor, mut content) = build("alpha beta gamma delta");
+
+        editor.handle_click(13, 0, &content);
+        editor.handle_mouse_up();
+        editor.set_cursor_range(Pos::from_row_column(0, 0), Pos::from_row_column(0, 3));
+        editor.handle_drag_with_mode(19, 0, DragMode::Word, &content);
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(0, 0), Pos::from_row_column(0, 22))
+        );
+
+        editor.handle_click(13, 0, &content);
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('x'),
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(content.get_content(), "alpha beta gaxmma delta");
+        editor.set_cursor_range(Pos::from_row_column(0, 0), Pos::from_row_column(0, 3));
+        editor.handle_drag_with_mode(2, 0, DragMode::Word, &content);
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(0, 0), Pos::from_row_column(0, 5))
+        );
+    }
+
+    #[test]
+    fn test_find_char_in_line_till_skips_the_adjacent_char() {
+        let (mut editor, content) = build("a,b,c");
+        // the cursor is already right before the first ','
+        editor.set_cursor_pos_r_c(0, 0);
+
+        assert!(editor.find_char_in_line(',', true, true, false, &content));
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 2));
+        // there is no further ',' which is not right next to the cursor
+        assert!(!editor.find_char_in_line(',', true, true, false, &content));
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 2));
+
+        editor.set_cursor_pos_r_c(0, 4);
+        assert!(editor.find_char_in_line(',', false, true, false, &content));
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 2));
+        assert!(!editor.find_char_in_line(',', false, true, false, &content));
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 2));
+    }
+
+    #[test]
+    fn test_mark_follows_local_edits() {
+        let (mut editor, mut content) = build("first\nsec█ond\nthird");
+        editor.set_mark();
+
+        // typing before the mark in its row
+        editor.set_cursor_pos_r_c(1, 0);
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('x'),
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(editor.mark(), Some(Pos::from_row_column(1, 4)));
+        // a new line above it
+        editor.set_cursor_pos_r_c(0, 2);
+        editor.handle_input_undoable(
+            EditorInputEvent::Enter,
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(content.get_content(), "fi\nrst\nxsecond\nthird");
+        assert_eq!(editor.mark(), Some(Pos::from_row_column(2, 4)));
+        // joining its row with the previous one
+        editor.set_cursor_pos_r_c(2, 0);
+        editor.handle_input_undoable(
+            EditorInputEvent::Backspace,
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(content.get_content(), "fi\nrstxsecond\nthird");
+        assert_eq!(editor.mark(), Some(Pos::from_row_column(1, 7)));
+        // moving its row down
+        editor.handle_input_undoable(EditorInputEvent::Down, InputModifiers::alt(), &mut content);
+        assert_eq!(content.get_content(), "fi\nthird\nrstxsecond");
+        assert_eq!(editor.mark(), Some(Pos::from_row_column(2, 7)));
+        // edits after it do not move it
+        editor.set_cursor_pos_r_c(2, 10);
+        editor.handle_input_undoable(
+            EditorInputEvent::Char('!'),
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(editor.mark(), Some(Pos::from_row_column(2, 7)));
+
+        editor.set_cursor_pos_r_c(0, 0);
+        assert!(editor.select_to_mark(&content));
+        assert_eq!(
+            editor.get_selected_text(&content).as_deref(),
+            Some("fi\nthird\nrstxsec")
+        );
+    }
+
+    #[test]
+    fn test_soft_breaks_follow_pastes_deletions_and_line_splits() {
+        let (mut editor, mut content) = build("");
+        let init = |editor: &mut Editor, content: &mut EditorContent<usize>| {
+            content.init_with("first\nabcdefgh\nthird");
+            content.add_soft_break(Pos::from_row_column(1, 3));
+            content.add_soft_break(Pos::from_row_column(1, 6));
+            editor.set_cursor_pos_r_c(1, 1);
+        };
+
+        init(&mut editor, &mut content);
+        editor.insert_text_undoable("12", &mut content);
+        assert_eq!(content.get_content(), "first\na12bcdefgh\nthird");
+        assert_eq!(content.soft_breaks(1), &[5, 8]);
+
+        // the breaks go with the moved text into the last pasted row
+        init(&mut editor, &mut content);
+        editor.insert_text_undoable("x\ny", &mut content);
+        assert_eq!(content.get_content(), "first\nax\nybcdefgh\nthird");
+        assert!(content.soft_breaks(1).is_empty());
+        assert_eq!(content.soft_breaks(2), &[3, 6]);
+
+        // the break inside the deleted text is dropped
+        init(&mut editor, &mut content);
+        editor.set_cursor_range(Pos::from_row_column(1, 2), Pos::from_row_column(1, 4));
+        editor.handle_input_undoable(EditorInputEvent::Del, InputModifiers::none(), &mut content);
+        assert_eq!(content.get_content(), "first\nabefgh\nthird");
+        assert_eq!(content.soft_breaks(1), &[4]);
+
+        init(&mut editor, &mut content);
+        editor.set_cursor_pos_r_c(1, 4);
+        editor.handle_input_undoable(
+            EditorInputEvent::Enter,
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(content.get_content(), "first\nabcd\nefgh\nthird");
+        assert_eq!(content.soft_breaks(1), &[3]);
+        assert_eq!(content.soft_breaks(2), &[2]);
+
+        // merging the rows back
+        editor.handle_input_undoable(
+            EditorInputEvent::Backspace,
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_eq!(content.get_content(), "first\nabcdefgh\nthird");
+        assert_eq!(content.soft_breaks(1), &[3, 6]);
+    }
+
+    #[test]
+    fn test_insert_empty_row_at_max_line_count_is_rejected() {
+        let (mut editor, mut content) = build("first\nsecond\nthird");
+        editor.set_max_line_count(Some(3));
+
+        editor.set_cursor_pos_r_c(1, 2);
+        let modif = editor.handle_input_undoable(
+            EditorInputEvent::Enter,
+            InputModifiers::ctrl_shift(),
+            &mut content,
+        );
+        assert_eq!(modif, None);
+        assert_eq!(editor.last_input_effect(), InputEffect::Rejected);
+        assert_eq!(content.get_content(), "first\nsecond\nthird");
+
+        // even with a multiline selection
+        editor.set_selection_save_col(Selection::range(
+            Pos::from_row_column(1, 3),
+            Pos::from_row_column(2, 2),
+        ));
+        let modif = editor.handle_input_undoable(
+            EditorInputEvent::Enter,
+            InputModifiers::ctrl_shift(),
+            &mut content,
+        );
+        assert_eq!(modif, None);
+        assert_eq!(editor.last_input_effect(), InputEffect::Rejected);
+        assert_eq!(content.get_content(), "first\nsecond\nthird");
+
+        // below the limit it is inserted
+        editor.set_max_line_count(Some(4));
+        let modif = editor.handle_input_undoable(
+            EditorInputEvent::Enter,
+            InputModifiers::ctrl_shift(),
+            &mut content,
+        );
+        assert!(modif.is_some());
+        assert_eq!(content.get_content(), "first\n\nsecond\nthird");
+    }
+
+    #[test]
+    fn test_diff_snapshot_of_single_char_edit_stores_one_row() {
+        let (mut before, mut before_content) = build("first line\nsecond█ line\nthird line");
+        let (after, after_content) = build("first line\nsecondX█ line\nthird line");
+
+        let snapshot = Editor::diff_snapshot(&before, &before_content, &after, &after_content);
+        let row = |text: &str| RowSnapshot {
+            chars: text.chars().collect(),
+            data: 0,
+            soft_breaks: vec![],
+            bookmarked: false,
+        };
+        assert_eq!(
+            snapshot,
+            RowsSnapshot {
+                first_row: 1,
+                rows_before: vec![row("second line")],
+                rows_after: vec![row("secondX line")],
+                selection_before: Selection::single_r_c(1, 6),
+                selection_after: Selection::single_r_c(1, 7),
+                line_ending_before: LineEnding::Lf,
+                line_ending_after: LineEnding::Lf,
+            }
+        );
+
+        // applying it forward and backward
+        before_content
+            .redo_stack
+            .push(vec![EditorCommand::ReplaceRows(Box::new(snapshot))]);
+        before.redo(&mut before_content);
+        assert_eq!(
+            before_content.get_content(),
+            "first line\nsecondX line\nthird line"
+        );
+        assert_eq!(before.get_selection(), Selection::single_r_c(1, 7));
+        before.undo(&mut before_content);
+        assert_eq!(
+            before_content.get_content(),
+            "first line\nsecond line\nthird line"
+        );
+        assert_eq!(before.get_selection(), Selection::single_r_c(1, 6));
+    }
+
+    #[test]
+    fn test_set_content_preserving_cursor_is_undoable() {
+        let (mut editor, mut content) = build("a=1\nb = █a*2\nc=b\nd=c");
+
+        editor.set_content_preserving_cursor("a = 1\nb = a*2\nc = b\nd=c", &mut content);
+        assert_eq!(content.get_content(), "a = 1\nb = a*2\nc = b\nd=c");
+        // only the first three rows are stored, the unchanged last one is not
+        assert_eq!(content.undo_stack.len(), 1);
+        match &content.undo_stack[0][..] {
+            [EditorCommand::ReplaceRows(snapshot)] => {
+                assert_eq!(snapshot.first_row, 0);
+                assert_eq!(snapshot.rows_before.len(), 3);
+                assert_eq!(snapshot.rows_after.len(), 3);
+            }
+            commands => panic!("{:?}", commands),
+        }
+
+        editor.undo(&mut content);
+        assert_markup(&editor, &content, "a=1\nb = █a*2\nc=b\nd=c");
+        editor.redo(&mut content);
+        assert_eq!(content.get_content(), "a = 1\nb = a*2\nc = b\nd=c");
+    }
+
+    #[test]
+    fn test_shift_tab_dedent_uses_the_tab_width_and_keeps_tabs() {
+        let (mut editor, mut content) = build("    abc\n\tdef\n   ghi\n  jkl");
+        editor.set_tab_width(2);
+        editor.set_selection_save_col(Selection::range(
+            Pos::from_row_column(0, 0),
+            Pos::from_row_column(3, 0),
+        ));
+        editor.handle_input_undoable(EditorInputEvent::Tab, InputModifiers::shift(), &mut content);
+        // the row where the selection ends at column 0 is not dedented
+        assert_markup(&editor, &content, "❱  abc\n\tdef\n ghi\n❰  jkl");
+
+        // Tab indents to the next tab stop of the configured width
+        editor.set_cursor_pos_r_c(2, 1);
+        editor.handle_input_undoable(EditorInputEvent::Tab, InputModifiers::none(), &mut content);
+        assert_eq!(content.get_content(), "  abc\n\tdef\n  ghi\n  jkl");
+    }
+
+    #[test]
+    fn test_alt_down_does_not_move_the_row_where_the_selection_ends_at_column_0() {
+        let (mut editor, mut content) = build("❱a\nb\n❰c\nd");
+        editor.handle_input_undoable(EditorInputEvent::Down, InputModifiers::alt(), &mut content);
+        assert_markup(&editor, &content, "c\n❱a\nb\n❰d");
+    }
+
+    #[test]
+    fn test_alt_up_no_undo_is_not_undoable() {
+        let (mut editor, mut content) = build("a\nb\nc█");
+        editor.handle_input_no_undo(EditorInputEvent::Up, InputModifiers::alt(), &mut content);
+        assert_eq!(content.get_content(), "a\nc\nb");
+        editor.undo(&mut content);
+        assert_eq!(content.get_content(), "a\nc\nb");
+    }
+
+    #[test]
+    fn test_page_down_saves_the_column_for_the_next_up() {
+        let (mut editor, mut content) = build("a█bcdef\nabcdef\nabc");
+        editor.set_page_size(10);
+        editor.handle_input_undoable(
+            EditorInputEvent::PageDown,
+            InputModifiers::none(),
+            &mut content,
+        );
+        assert_markup(&editor, &content, "abcdef\nabcdef\nabc█");
+        editor.handle_input_undoable(EditorInputEvent::Up, InputModifiers::none(), &mut content);
+        assert_markup(&editor, &content, "abcdef\nabc█def\nabc");
+    }
+
+    #[test]
+    fn test_undo_of_set_content_preserving_cursor_restores_the_rows_and_the_line_ending() {
+        let (mut editor, mut content) = build("a=1\nb=2\nc=3");
+        *content.mut_data(1) = 42;
+        content.toggle_bookmark(1);
+        assert!(content.add_soft_break(Pos::from_row_column(1, 1)));
+
+        editor.set_content_preserving_cursor("a=1\r\nb = 2\r\nc=3", &mut content);
+        assert_eq!(content.get_content(), "a=1\nb = 2\nc=3");
+        assert_eq!(content.detected_line_ending(), LineEnding::CrLf);
+        assert_eq!(*content.get_data(1), 0);
+        assert_eq!(content.bookmarks(), vec![]);
+
+        editor.undo(&mut content);
+        assert_markup(&editor, &content, "█a=1\nb=2\nc=3");
+        assert_eq!(content.detected_line_ending(), LineEnding::Lf);
+        assert_eq!(*content.get_data(1), 42);
+        assert_eq!(content.bookmarks(), vec![1]);
+        assert_eq!(content.soft_breaks(1), &[1]);
+
+        editor.redo(&mut content);
+        assert_eq!(content.get_content(), "a=1\nb = 2\nc=3");
+        assert_eq!(content.detected_line_ending(), LineEnding::CrLf);
+    }
+
+    #[test]
+    fn test_set_content_preserving_cursor_changing_only_the_line_ending_is_undoable() {
+        let (mut editor, mut content) = build("a=1\nb=2");
+        editor.set_content_preserving_cursor("a=1\r\nb=2", &mut content);
+        assert_eq!(content.detected_line_ending(), LineEnding::CrLf);
+        editor.undo(&mut content);
+        assert_eq!(content.detected_line_ending(), LineEnding::Lf);
+        assert_eq!(content.get_content(), "a=1\nb=2");
+    }
 }
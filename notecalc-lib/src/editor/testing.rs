@@ -0,0 +1,206 @@
+//! A tiny markup language to describe the state of an editor in tests.
+//! '█' marks the cursor's position, '❱' and '❰' mark the start and the end
+//! of a selection. Every other character is the content itself.
+use crate::editor::editor::{Editor, Pos, Selection};
+use crate::editor::editor_content::EditorContent;
+
+pub(crate) const CURSOR_MARKER: char = '█';
+// U+2770	❰	e2 9d b0	HEAVY LEFT-POINTING ANGLE BRACKET OR­NA­MENT
+pub(crate) const SELECTION_START_MARK: char = '❱';
+pub(crate) const SELECTION_END_MARK: char = '❰';
+
+pub(crate) fn build(markup: &str) -> (Editor, EditorContent<usize>) {
+    let mut content = EditorContent::<usize>::new(80);
+    let mut editor = Editor::new(&mut content);
+    load_markup(&mut editor, &mut content, markup);
+    (editor, content)
+}
+
+/// we can assume here that the markup does not contain illegal or complex input
+/// so we can just set it as it is
+pub(crate) fn load_markup(editor: &mut Editor, content: &mut EditorContent<usize>, markup: &str) {
+    let mut selection_found = false;
+    let mut selection_start = Pos { row: 0, column: 0 };
+    let mut selection_end = Pos { row: 0, column: 0 };
+    for (row_index, line) in markup.lines().enumerate() {
+        let mut row_len = 0;
+        for char in line.chars() {
+            if char == CURSOR_MARKER {
+                editor.set_cursor_pos_r_c(row_index, row_len);
+            } else if char == SELECTION_START_MARK {
+                selection_found = true;
+                selection_start = Pos {
+                    row: row_index,
+                    column: row_len,
+                };
+            } else if char == SELECTION_END_MARK {
+                selection_end = Pos {
+                    row: row_index,
+                    column: row_len,
+                };
+            } else {
+                content.set_char(row_index, row_len, char);
+                row_len += 1;
+            }
+        }
+        if content.line_lens.len() <= row_index {
+            content.push_line();
+        }
+        content.set_line_len(row_index, row_len);
+    }
+    if selection_found {
+        editor.set_cursor_range(selection_start, selection_end);
+    }
+}
+
+/// asserts that the content matches the markup and returns the selection described by it
+pub(crate) fn assert_content_markup(content: &EditorContent<usize>, markup: &str) -> Selection {
+    let mut expected_cursor = Selection::single_r_c(0, 0);
+    let mut expected_selection_start = Pos { row: 0, column: 0 };
+    let mut expected_selection_end = Pos { row: 0, column: 0 };
+    let mut selection_found = false;
+    for (row_index, expected_line) in markup.lines().enumerate() {
+        let mut expected_row_len = 0;
+        for char in expected_line.chars() {
+            if char == CURSOR_MARKER {
+                expected_cursor = Selection::single_r_c(row_index, expected_row_len);
+            } else if char == SELECTION_START_MARK {
+                selection_found = true;
+                expected_selection_start = Pos {
+                    row: row_index,
+                    column: expected_row_len,
+                }
+            } else if char == SELECTION_END_MARK {
+                expected_selection_end = Pos {
+                    row: row_index,
+                    column: expected_row_len,
+                }
+            } else {
+                assert_eq!(
+                    content.get_line_chars(row_index)[expected_row_len],
+                    char,
+                    "row: {}, column: {}, chars: {:?}",
+                    row_index,
+                    expected_row_len,
+                    content
+                        .get_line_chars(row_index)
+                        .iter()
+                        .take_while(|it| **it as u8 != 0)
+                        .collect::<String>()
+                );
+                expected_row_len += 1;
+            }
+        }
+
+        assert_eq!(
+            markup.lines().count(),
+            content.line_count(),
+            "Expected line count differs. Current content: {}",
+            content.get_content()
+        );
+        assert!(
+            content.line_lens[row_index] <= expected_row_len,
+            "Line {}, Actual data is longer: {:?}",
+            row_index,
+            &content.get_line_chars(row_index)[expected_row_len..content.line_lens[row_index]]
+        );
+        assert!(
+            content.line_lens[row_index] >= expected_row_len,
+            "Line {}, Actual data is shorter,  actual: {:?} \n, expected: {:?}",
+            row_index,
+            &content.get_line_chars(row_index)[0..content.line_lens[row_index]],
+            &expected_line[content.line_lens[row_index]..expected_row_len]
+        );
+    }
+    if selection_found {
+        Selection {
+            start: expected_selection_start,
+            end: Some(expected_selection_end),
+        }
+    } else {
+        expected_cursor
+    }
+}
+
+pub(crate) fn assert_selection(editor: &Editor, expected: Selection) {
+    if let Some(expected_end) = expected.end {
+        assert_eq!(
+            editor.get_selection().start,
+            expected.start,
+            "Selection start"
+        );
+        assert!(editor.get_selection().is_range());
+        assert_eq!(
+            editor.get_selection().end.unwrap(),
+            expected_end,
+            "Selection end"
+        );
+    } else {
+        assert_eq!(editor.get_selection(), expected, "Cursor");
+    }
+}
+
+pub(crate) fn assert_markup(editor: &Editor, content: &EditorContent<usize>, markup: &str) {
+    let expected = assert_content_markup(content, markup);
+    assert_selection(editor, expected);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::editor::{Pos, Selection};
+    use crate::editor::testing::{assert_content_markup, assert_markup, build};
+
+    #[test]
+    fn test_build_with_cursor() {
+        let (editor, content) = build("abc█def");
+        assert_eq!(content.get_content(), "abcdef");
+        assert_eq!(editor.get_selection(), Selection::single_r_c(0, 3));
+        assert_markup(&editor, &content, "abc█def");
+    }
+
+    #[test]
+    fn test_build_with_selection() {
+        let (editor, content) = build("a❱bc❰d");
+        assert_eq!(content.get_content(), "abcd");
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(0, 1), Pos::from_row_column(0, 3))
+        );
+        assert_markup(&editor, &content, "a❱bc❰d");
+    }
+
+    #[test]
+    fn test_build_with_backward_multiline_selection() {
+        let (editor, content) = build("first❰\nsecond\nth❱ird");
+        assert_eq!(content.line_count(), 3);
+        assert_eq!(content.get_content(), "first\nsecond\nthird");
+        assert_eq!(
+            editor.get_selection(),
+            Selection::range(Pos::from_row_column(2, 2), Pos::from_row_column(0, 5))
+        );
+        assert_markup(&editor, &content, "first❰\nsecond\nth❱ird");
+    }
+
+    #[test]
+    fn test_assert_content_markup_returns_the_expected_cursor() {
+        let (_editor, content) = build("first\nsecond");
+        assert_eq!(
+            assert_content_markup(&content, "first\nsec█ond"),
+            Selection::single_r_c(1, 3)
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_assert_markup_fails_on_different_content() {
+        let (editor, content) = build("abc█");
+        assert_markup(&editor, &content, "abd█");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_assert_markup_fails_on_different_cursor() {
+        let (editor, content) = build("abc█");
+        assert_markup(&editor, &content, "ab█c");
+    }
+}
@@ -29,7 +29,7 @@ use crate::calc::{
 };
 use crate::consts::{LINE_NUM_CONSTS, LINE_NUM_CONSTS2, LINE_NUM_CONSTS3};
 use crate::editor::editor::{
-    Editor, EditorInputEvent, InputModifiers, Pos, RowModificationType, Selection,
+    Editor, EditorInputEvent, InputModifiers, OverflowPolicy, Pos, RowModificationType, Selection,
 };
 use crate::editor::editor_content::EditorContent;
 use crate::matrix::MatrixData;
@@ -1414,11 +1414,14 @@ pub const EMPTY_FILE_DEFUALT_CONTENT: &str = "\n\n\n\n\n\n\n\n\n\n";
 impl NoteCalcApp {
     pub fn new(client_width: usize, client_height: usize) -> NoteCalcApp {
         let mut editor_content = EditorContent::new(MAX_EDITOR_WIDTH);
+        let mut editor = Editor::new(&mut editor_content);
+        // PageUp/PageDown jump to the beginning/end of the note
+        editor.set_page_size(MAX_LINE_COUNT);
         NoteCalcApp {
             line_reference_chooser: None,
             client_width,
             result_panel_width_percent: DEFAULT_RESULT_PANEL_WIDTH_PERCENT,
-            editor: Editor::new(&mut editor_content),
+            editor,
             editor_content,
             matrix_editing: None,
             line_id_generator: 1,
@@ -1928,6 +1931,7 @@ impl NoteCalcApp {
     }
 
     pub fn handle_mouse_up(&mut self) {
+        self.editor.handle_mouse_up();
         match self.mouse_state {
             Some(MouseClickType::RightGutterIsDragged) => {}
             Some(MouseClickType::ClickedInEditor) => {}
@@ -2688,7 +2692,7 @@ impl NoteCalcApp {
         let prev_row = self.editor.get_selection().get_cursor_pos().row;
         match self
             .editor
-            .insert_text_undoable(&text, &mut self.editor_content)
+            .paste(&text, OverflowPolicy::Wrap, &mut self.editor_content)
         {
             Some(modif) => {
                 if self.editor.get_selection().get_cursor_pos().row >= MAX_LINE_COUNT {
@@ -2825,7 +2829,10 @@ impl NoteCalcApp {
         let prev_selection = self.editor.get_selection();
         let prev_row = self.editor.get_selection().get_cursor_pos().row;
         let mut refactor_me = false;
-        let modif = if self.matrix_editing.is_none() && modifiers.alt {
+        // Alt+Up/Down choose a line reference, with Shift they go to the editor and move the lines
+        let moves_lines =
+            modifiers.shift && (input == EditorInputEvent::Up || input == EditorInputEvent::Down);
+        let modif = if self.matrix_editing.is_none() && modifiers.alt && !moves_lines {
             handle_input_with_alt(&mut *self, input)
         } else if self.is_matrix_editing_or_need_to_create_one(
             input,
@@ -3667,10 +3674,7 @@ impl NoteCalcApp {
 
     fn handle_parenthesis_completion<'b>(&mut self, input: &EditorInputEvent) -> bool {
         let closing_char = match input {
-            EditorInputEvent::Char('(') => Some(')'),
-            EditorInputEvent::Char('[') => Some(']'),
-            EditorInputEvent::Char('{') => Some('}'),
-            EditorInputEvent::Char('\"') => Some('\"'),
+            EditorInputEvent::Char(ch) => self.editor.auto_pair_closing_for(*ch),
             _ => None,
         };
         if let Some(closing_char) = closing_char {
@@ -3681,10 +3685,10 @@ impl NoteCalcApp {
             {
                 let cursor = self.editor.get_selection().get_cursor_pos();
                 let next_char = self.editor_content.get_char(cursor.row, cursor.column);
+                // a quote in front of the cursor does not allow completion, since it might
+                // just as well be an opening one
                 let closing_paren_allowed = next_char.is_whitespace()
-                    || next_char == ')'
-                    || next_char == ']'
-                    || next_char == '}'
+                    || self.editor.is_auto_pair_closing(next_char)
                     || self.editor.is_cursor_at_eol(&self.editor_content);
                 if !closing_paren_allowed {
                     return true;
@@ -3737,13 +3741,8 @@ impl NoteCalcApp {
             None
         };
         if *input == EditorInputEvent::Backspace {
-            let closing_char = match char_in_front_of_cursur {
-                Some('(') => Some(')'),
-                Some('[') => Some(']'),
-                Some('{') => Some('}'),
-                Some('\"') => Some('\"'),
-                _ => None,
-            };
+            let closing_char =
+                char_in_front_of_cursur.and_then(|ch| self.editor.auto_pair_closing_for(ch));
             if let Some(closing_char) = closing_char {
                 let cursor_pos = self.editor.get_selection().get_cursor_pos();
                 if !self.editor.is_cursor_at_eol(&self.editor_content)
@@ -3779,10 +3778,7 @@ impl NoteCalcApp {
         if let Some((start, end)) = prev_selection.is_range_ordered() {
             let single_line = start.row == end.row;
             let closing_char = match input {
-                EditorInputEvent::Char('(') => Some(')'),
-                EditorInputEvent::Char('[') => Some(']'),
-                EditorInputEvent::Char('{') => Some('}'),
-                EditorInputEvent::Char('\"') => Some('\"'),
+                EditorInputEvent::Char(ch) => self.editor.auto_pair_closing_for(*ch),
                 _ => None,
             };
             if let Some(closing_char) = closing_char {
@@ -12777,6 +12773,55 @@ asd",
         }
     }
 
+    #[test]
+    fn test_parens_are_inserted_before_closing_chars_but_not_before_quotes() {
+        let test = create_app3(84, 36);
+        test.paste("a)");
+        test.input(EditorInputEvent::Home, InputModifiers::none());
+        test.input(EditorInputEvent::Right, InputModifiers::none());
+        test.input(EditorInputEvent::Char('('), InputModifiers::none());
+        assert_eq!("a())", &test.get_editor_content());
+
+        let test = create_app3(84, 36);
+        test.paste("a\"");
+        test.input(EditorInputEvent::Home, InputModifiers::none());
+        test.input(EditorInputEvent::Right, InputModifiers::none());
+        test.input(EditorInputEvent::Char('('), InputModifiers::none());
+        assert_eq!("a(\"", &test.get_editor_content());
+    }
+
+    #[test]
+    fn test_custom_auto_pairs() {
+        let test = create_app3(84, 36);
+        test.mut_app()
+            .editor
+            .set_auto_pairs(&[('«', '»'), ('(', ')')]);
+        test.paste("");
+        test.input(EditorInputEvent::Char('«'), InputModifiers::none());
+        test.input(EditorInputEvent::Char('['), InputModifiers::none());
+        assert_eq!("«[»", &test.get_editor_content());
+        // '»' closes a pair, so a paren is completed in front of it
+        test.input(EditorInputEvent::Char('('), InputModifiers::none());
+        assert_eq!("«[()»", &test.get_editor_content());
+    }
+
+    #[test]
+    fn test_alt_shift_down_moves_the_line_instead_of_choosing_a_line_ref() {
+        let test = create_app3(84, 36);
+        test.paste("a\nb\nc");
+        test.set_cursor_row_col(0, 1);
+        test.input(
+            EditorInputEvent::Down,
+            InputModifiers {
+                shift: true,
+                ctrl: false,
+                alt: true,
+            },
+        );
+        assert_eq!("b\na\nc", &test.get_editor_content());
+        assert!(test.app().line_reference_chooser.is_none());
+    }
+
     #[test]
     fn test_insert_closing_parenthesis_around_selected_text() {
         for (tested_opening_char, expected_closing_char) in